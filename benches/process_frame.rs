@@ -0,0 +1,128 @@
+//! Benchmarks of the audio pipeline with a growing number of sources in each level.
+//!
+//! Run with `cargo bench`. The sources of each level are replaced by 1 to 32 spatial sources in a
+//! ring around the listener. For each level and number of sources, the time spent per frame in the
+//! direct simulation, the reflections simulation, and the effects and mixing is averaged over the
+//! rendered frames, along with the real-time factor: how many times faster than real time the
+//! frames are rendered. The simulator is built with room for every source, so that none of them
+//! is virtualized by the voice manager.
+
+use std::time::Duration;
+
+use audionimbus_demo::{
+    audio::{self, Audio, AudioSource, NonSpatialAudioSource, ProcessingTimes},
+    clip::AudioClip,
+    level::LEVELS,
+    offline,
+};
+use bevy::prelude::*;
+
+const SOURCE_COUNTS: [usize; 4] = [1, 4, 8, 32];
+const CLIP: &str = "piano.raw";
+/// Distance from the listener to the sources, in meters.
+const RING_RADIUS: f32 = 3.0;
+/// Frames rendered before measuring, so that the voices and reflections settle.
+const WARMUP_FRAMES: usize = 20;
+const MEASURED_FRAMES: usize = 200;
+
+/// Mean times spent per frame.
+#[derive(Default)]
+struct FrameTimes {
+    direct: Duration,
+    reflections: Duration,
+    dsp: Duration,
+}
+
+fn main() {
+    println!(
+        "{:<14}{:>9}{:>14}{:>19}{:>11}{:>18}",
+        "level", "sources", "direct (ms)", "reflections (ms)", "DSP (ms)", "real-time factor"
+    );
+    for (index, (name, _)) in LEVELS.iter().enumerate() {
+        for num_sources in SOURCE_COUNTS {
+            let times = bench(index, num_sources);
+            let real_time_factor = offline::frame_duration().as_secs_f64()
+                / (times.direct + times.reflections + times.dsp).as_secs_f64();
+            println!(
+                "{:<14}{:>9}{:>14.3}{:>19.3}{:>11.3}{:>18.1}",
+                name,
+                num_sources,
+                times.direct.as_secs_f64() * 1000.0,
+                times.reflections.as_secs_f64() * 1000.0,
+                times.dsp.as_secs_f64() * 1000.0,
+                real_time_factor
+            );
+        }
+    }
+}
+
+/// Returns the mean times per frame with `num_sources` sources in the level.
+fn bench(level_index: usize, num_sources: usize) -> FrameTimes {
+    let max_num_sources = *SOURCE_COUNTS.iter().max().unwrap();
+    let mut app = offline::app_with(audio::Plugin {
+        offline: true,
+        // Leaves room for the listener source.
+        max_num_sources: Some(max_num_sources + 1),
+        ..Default::default()
+    });
+    offline::load_level(&mut app, level_index);
+    let listener = offline::listener_transform(&mut app);
+    replace_sources(&mut app, listener.translation, num_sources);
+
+    let listener_path = |_| listener;
+    offline::render(&mut app, WARMUP_FRAMES, listener_path);
+
+    let mut times = FrameTimes::default();
+    for _ in 0..MEASURED_FRAMES {
+        offline::render(&mut app, 1, listener_path);
+        let processing_times = app.world().resource::<ProcessingTimes>();
+        times.direct += processing_times.direct;
+        times.reflections += processing_times.reflections;
+        times.dsp += processing_times.dsp;
+    }
+    FrameTimes {
+        direct: times.direct / MEASURED_FRAMES as u32,
+        reflections: times.reflections / MEASURED_FRAMES as u32,
+        dsp: times.dsp / MEASURED_FRAMES as u32,
+    }
+}
+
+/// Despawns the sources of the level, and spawns `num_sources` repeating sources evenly spread
+/// on a ring around `center`.
+fn replace_sources(app: &mut App, center: Vec3, num_sources: usize) {
+    let world = app.world_mut();
+    // Loaded before despawning the level sources, so that the clip they share stays loaded.
+    let clip: Handle<AudioClip> = world.resource::<AssetServer>().load(CLIP);
+
+    let level_sources = world
+        .query_filtered::<Entity, Or<(With<AudioSource>, With<NonSpatialAudioSource>)>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for entity in level_sources {
+        world.despawn(entity);
+    }
+
+    for i in 0..num_sources {
+        let angle = std::f32::consts::TAU * i as f32 / num_sources as f32;
+        let position = center + Vec3::new(angle.cos(), 0.0, angle.sin()) * RING_RADIUS;
+        let source = world.resource_mut::<Audio>().create_source(
+            audionimbus::SimulationFlags::DIRECT | audionimbus::SimulationFlags::REFLECTIONS,
+        );
+        world.spawn((
+            Transform::from_translation(position),
+            AudioSource {
+                is_repeating: true,
+                ..AudioSource::new(source, clip.clone())
+            },
+        ));
+    }
+
+    while !app
+        .world()
+        .resource::<AssetServer>()
+        .is_loaded_with_dependencies(&clip)
+    {
+        std::thread::sleep(Duration::from_millis(10));
+        app.update();
+    }
+}
@@ -1,34 +1,160 @@
-use std::time::Duration;
+use std::{
+    ops::Range,
+    path::Path,
+    time::{Duration, Instant},
+};
 
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    time::{TimeSystems, TimeUpdateStrategy},
+    transform::TransformSystems,
+};
 use itertools::izip;
 use rodio::{OutputStream, Sink, Source};
 
+use crate::clip::{AudioClip, ClipLayout, RawClipLoader, WavClipLoader};
+use crate::clock::{AudioClock, PlaybackSchedule};
+use crate::cue::{CuePick, Sound};
+use crate::dynamics::MasterDynamics;
+use crate::geometry::{
+    write_obj, AcousticMesh, InstancedMeshes, RayHit, StaticAcousticMesh, StaticMeshId,
+    StaticMeshes,
+};
+use crate::lod::LodTier;
+use crate::metering::{Meter, Meters};
+use crate::mixer::{AudioBus, Mixer};
+use crate::recording::Recorder;
+use crate::resample;
+use crate::voices::{apply_fade, VoiceState};
+
 pub const FRAME_SIZE: usize = 1024;
 pub const SAMPLING_RATE: usize = 48000;
 pub const NUM_CHANNELS: usize = 2;
 pub const AMBISONICS_ORDER: usize = 2;
 pub const AMBISONICS_NUM_CHANNELS: usize = (AMBISONICS_ORDER + 1).pow(2);
+/// Sources the simulator holds by default, including the listener source.
+pub const MAX_NUM_SOURCES: usize = 8;
+/// Range of the playback rate of sources.
+pub const MIN_PITCH: f32 = 0.25;
+pub const MAX_PITCH: f32 = 4.0;
 pub const GAIN_FACTOR_DIRECT: f32 = 1.0;
 pub const GAIN_FACTOR_REFLECTIONS: f32 = 0.3;
 pub const GAIN_FACTOR_REVERB: f32 = 0.1;
+/// Frames queued on the output device past which deterministic mode waits for it to catch up.
+const MAX_QUEUED_FRAMES: usize = 3;
 
 #[derive(Resource)]
 pub struct Audio {
     pub context: audionimbus::Context,
 
     pub scene: audionimbus::Scene,
+    pub static_meshes: StaticMeshes,
+    pub instanced_meshes: InstancedMeshes,
     pub simulator: audionimbus::Simulator<audionimbus::Direct, audionimbus::Reflections>,
+    /// Sources the simulator can hold, including the listener source.
+    pub max_num_sources: usize,
+    /// Whether sources left the simulator since it was last committed.
+    pub needs_commit: bool,
     pub hrtf: audionimbus::Hrtf,
     pub direct_effect: audionimbus::DirectEffect,
     pub reflection_effect: audionimbus::ReflectionEffect,
+    pub parametric_reflection_effect: audionimbus::ReflectionEffect,
     pub reverb_effect: audionimbus::ReflectionEffect,
     pub ambisonics_encode_effect: audionimbus::AmbisonicsEncodeEffect,
     pub ambisonics_decode_effect: audionimbus::AmbisonicsDecodeEffect,
-    pub sink: Sink,
+    pub output: AudioOutput,
     pub timer: Timer,
 }
 
+/// Destination of the rendered frames.
+pub enum AudioOutput {
+    /// Played on the default output device, as many frames per update as their duration fits in
+    /// the elapsed time.
+    Device(Sink),
+    /// Rendered one frame per update and kept in memory as interleaved stereo, for offline
+    /// rendering.
+    Offline(Vec<f32>),
+}
+
+/// Present when the app runs deterministically: an update either advances the time by exactly one
+/// frame and renders that frame, or leaves both still, so that the time, the camera and the sources
+/// move in lockstep with the [`AudioClock`](crate::clock::AudioClock). The random numbers of the
+/// cues are seeded with [`DeterministicMode::SEED`], and the simulation runs on a single thread.
+///
+/// Steam Audio exposes no seed for the rays it traces, so the reflections are only reproducible
+/// as far as its ray tracer is; `tests/determinism.rs` checks that two offline renders are
+/// identical.
+///
+/// Frames played on a device are paced by it: updates leave the time still while it has more than
+/// [`MAX_QUEUED_FRAMES`] frames queued.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct DeterministicMode;
+
+impl DeterministicMode {
+    pub const SEED: u64 = 0;
+}
+
+impl Audio {
+    /// Creates a source for an [`AudioSource`]. It is added to the simulator by the voice
+    /// manager once it becomes a real voice.
+    pub fn create_source(&mut self, flags: audionimbus::SimulationFlags) -> audionimbus::Source {
+        audionimbus::Source::try_new(&self.simulator, &audionimbus::SourceSettings { flags })
+            .unwrap()
+    }
+
+    /// Adds `mesh` to the scene. The scene must be committed for the change to take effect.
+    pub fn add_static_mesh(&mut self, mesh: AcousticMesh) -> StaticMeshId {
+        let static_mesh = mesh.try_to_static_mesh(&self.scene).unwrap();
+        self.scene.add_static_mesh(&static_mesh);
+        self.static_meshes
+            .insert(StaticAcousticMesh { mesh, static_mesh })
+    }
+
+    /// Removes a mesh added with [`Audio::add_static_mesh`]. The scene must be committed for the
+    /// change to take effect.
+    pub fn remove_static_mesh(&mut self, id: StaticMeshId) {
+        if let Some(static_mesh) = self.static_meshes.meshes.remove(&id) {
+            self.scene.remove_static_mesh(&static_mesh.static_mesh);
+        }
+    }
+
+    /// Closest intersection of `ray` with the static and instanced meshes, within
+    /// `max_distance`.
+    pub fn raycast(&self, ray: Ray3d, max_distance: f32) -> Option<RayHit> {
+        let static_hits = self
+            .static_meshes
+            .meshes
+            .values()
+            .filter_map(|static_mesh| static_mesh.mesh.raycast(Mat4::IDENTITY, ray, max_distance));
+        let instanced_hits =
+            self.instanced_meshes.0.values().filter_map(|instance| {
+                instance.mesh.raycast(instance.transform, ray, max_distance)
+            });
+        static_hits
+            .chain(instanced_hits)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    /// Dumps the static and instanced meshes of the scene to an OBJ file and its MTL file.
+    pub fn export_obj(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let static_meshes = self.static_meshes.meshes.iter().map(|(id, static_mesh)| {
+            (
+                format!("static_{}", id.index()),
+                &static_mesh.mesh,
+                Mat4::IDENTITY,
+            )
+        });
+        let instanced_meshes = self.instanced_meshes.0.iter().map(|(entity, instance)| {
+            (
+                format!("instanced_{entity}"),
+                &instance.mesh,
+                instance.transform,
+            )
+        });
+        write_obj(path.as_ref(), static_meshes.chain(instanced_meshes))
+    }
+}
+
 pub struct AudioFrame {
     position: usize,
     data: Vec<f32>,
@@ -83,31 +209,464 @@ impl Source for AudioFrame {
 #[require(GlobalTransform)]
 pub struct AudioSource {
     pub source: audionimbus::Source,
-    pub data: Vec<audionimbus::Sample>, // Mono
+    pub sound: Sound,
+    /// Clip currently picked when `sound` is a cue.
+    pub cue_pick: Option<CuePick>,
     pub is_repeating: bool,
-    pub position: usize,
+    /// Playback position in the clip, in samples of the clip.
+    pub position: f64,
+    /// Playback rate, between [`MIN_PITCH`] and [`MAX_PITCH`]; 2 plays the clip an octave higher
+    /// and twice as fast. Clips play at their own sampling rate at a pitch of 1.
+    pub pitch: f32,
+    /// Radius of the source for volumetric occlusion, or `None` to trace a single occlusion ray.
+    pub occlusion_radius: Option<f32>,
+    /// Directivity pattern, oriented along the forward axis of the source.
+    pub directivity: Directivity,
+    /// Linear gain applied to the clip.
+    pub volume: f32,
+    /// Distance in meters between the left and right emitters of stereo clips, along the right
+    /// axis of the source.
+    pub spread: f32,
+    /// Sources with a higher priority get real voices first, regardless of audibility.
+    pub priority: i32,
+    /// Whether the source is simulated or virtual, managed by [`crate::voices`].
+    pub voice: VoiceState,
+    /// Level of detail of the reflections, managed by [`crate::lod`].
+    pub lod: LodTier,
+    /// Tier the reflections were rendered at in the latest frame. The next frame crossfades from
+    /// it when `lod` changes.
+    pub rendered_lod: LodTier,
+    /// Simulation results and effect output levels from the latest frame.
+    pub outputs: SourceOutputs,
+    /// Level of the decoded output of the source.
+    pub meter: Meter,
+}
+
+impl AudioSource {
+    pub fn new(source: audionimbus::Source, sound: impl Into<Sound>) -> Self {
+        Self {
+            source,
+            sound: sound.into(),
+            cue_pick: None,
+            is_repeating: false,
+            position: 0.0,
+            pitch: 1.0,
+            occlusion_radius: None,
+            directivity: Directivity::default(),
+            volume: 1.0,
+            spread: 1.0,
+            priority: 0,
+            voice: VoiceState::default(),
+            lod: LodTier::default(),
+            rendered_lod: LodTier::default(),
+            outputs: SourceOutputs::default(),
+            meter: Meter::default(),
+        }
+    }
+}
+
+/// Source played straight to the stereo output, without simulation or spatialization. Suited to
+/// music, narration and UI sounds, and does not count against the simulator's maximum number of
+/// sources.
+///
+/// Mono clips are panned and stereo clips balanced with `pan`. Ambisonic clips are played as
+/// soundfield beds instead, fixed in the world: they are rotated by the listener orientation and
+/// decoded like the spatial sources.
+#[derive(Component, Debug)]
+pub struct NonSpatialAudioSource {
+    pub sound: Sound,
+    /// See [`AudioSource::cue_pick`].
+    pub cue_pick: Option<CuePick>,
+    pub is_repeating: bool,
+    /// See [`AudioSource::position`].
+    pub position: f64,
+    /// See [`AudioSource::pitch`].
+    pub pitch: f32,
+    /// Linear gain applied to the clip.
+    pub volume: f32,
+    /// -1 is hard left, 0 is centered and 1 is hard right.
+    pub pan: f32,
+    /// Level of the output of the source.
+    pub meter: Meter,
+}
+
+impl NonSpatialAudioSource {
+    pub fn new(sound: impl Into<Sound>) -> Self {
+        Self {
+            sound: sound.into(),
+            cue_pick: None,
+            is_repeating: false,
+            position: 0.0,
+            pitch: 1.0,
+            volume: 1.0,
+            pan: 0.0,
+            meter: Meter::default(),
+        }
+    }
+
+    /// Left and right gains of a constant power pan law.
+    pub fn channel_gains(&self) -> [f32; NUM_CHANNELS] {
+        let angle = (self.pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+        [angle.cos(), angle.sin()]
+    }
+}
+
+/// Number of samples of `clip` per output sample when played at `pitch`.
+fn playback_step(clip: &AudioClip, pitch: f32) -> f64 {
+    pitch.clamp(MIN_PITCH, MAX_PITCH) as f64 * clip.sampling_rate as f64 / SAMPLING_RATE as f64
+}
+
+/// Advances `position` by `num_played` output samples, each `step` samples of the clip apart.
+/// Returns whether a non-repeating clip of `num_samples` samples has been played to the end.
+fn advance_position(
+    num_samples: usize,
+    position: &mut f64,
+    step: f64,
+    is_repeating: bool,
+    num_played: usize,
+) -> bool {
+    *position += num_played as f64 * step;
+    if is_repeating {
+        *position %= num_samples as f64;
+        false
+    } else {
+        // If there are no more audio samples to play back.
+        *position >= num_samples as f64
+    }
+}
+
+/// Reads the frame of each channel of `clip` starting at `position` and played at `pitch`, and
+/// advances `position`. Only the samples of the frame in `window` play the clip, the others are
+/// silent. Samples between those of the clip are interpolated by [`resample::interpolate`].
+/// Returns the frames and whether a non-repeating clip has been played to the end.
+fn next_frame(
+    clip: &AudioClip,
+    position: &mut f64,
+    pitch: f32,
+    is_repeating: bool,
+    window: Range<usize>,
+) -> (Vec<Vec<f32>>, bool) {
+    let num_samples = clip.num_samples();
+    let step = playback_step(clip, pitch);
+    let sample = |samples: &[f32], idx: i64| {
+        if is_repeating {
+            samples[idx.rem_euclid(num_samples as i64) as usize]
+        } else if (0..num_samples as i64).contains(&idx) {
+            samples[idx as usize]
+        } else {
+            // Silence before and after the clip.
+            0.0
+        }
+    };
+    let frame = clip
+        .channels
+        .iter()
+        .map(|samples| {
+            (0..FRAME_SIZE)
+                .map(|i| {
+                    if !window.contains(&i) {
+                        return 0.0;
+                    }
+                    let time = *position + (i - window.start) as f64 * step;
+                    resample::interpolate(|idx| sample(samples, idx), time, step)
+                })
+                .collect()
+        })
+        .collect();
+
+    let finished = advance_position(num_samples, position, step, is_repeating, window.len());
+    (frame, finished)
+}
+
+/// Handles a source whose clip has played to the end. Repeating sources playing a cue get a new
+/// clip picked, and other sources are despawned.
+fn end_clip(
+    commands: &mut Commands,
+    entity: Entity,
+    sound: &Sound,
+    is_repeating: bool,
+    position: &mut f64,
+    cue_pick: &mut Option<CuePick>,
+) {
+    if sound.is_cue() && is_repeating {
+        *position = 0.0;
+        *cue_pick = None;
+    } else {
+        // Despawning a spatial source frees its voice.
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Converts a frame of an AmbiX soundfield of any order to a planar buffer of order
+/// [`AMBISONICS_ORDER`] in Steam Audio's N3D normalization. Missing orders are silent and
+/// higher orders are dropped.
+fn ambix_to_n3d(frame: &[Vec<f32>]) -> Vec<f32> {
+    let mut container = vec![0.0; FRAME_SIZE * AMBISONICS_NUM_CHANNELS];
+    for (acn, (output, input)) in container
+        .chunks_exact_mut(FRAME_SIZE)
+        .zip(frame)
+        .enumerate()
+    {
+        let degree = (acn as f32).sqrt() as usize;
+        let sn3d_to_n3d = ((2 * degree + 1) as f32).sqrt();
+        for (output, input) in output.iter_mut().zip(input) {
+            *output = input * sn3d_to_n3d;
+        }
+    }
+    container
+}
+
+/// Blend between an omnidirectional and a dipole pattern, as modeled by Steam Audio.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Directivity {
+    /// 0 is omnidirectional, 1 is a dipole, 0.5 is a cardioid.
+    pub dipole_weight: f32,
+    /// Sharpness of the dipole; higher values are more focused.
+    pub dipole_power: f32,
+}
+
+impl Directivity {
+    /// Attenuation towards a direction making `angle` radians with the forward axis.
+    pub fn gain(&self, angle: f32) -> f32 {
+        ((1.0 - self.dipole_weight) + self.dipole_weight * angle.cos())
+            .abs()
+            .powf(self.dipole_power)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SourceOutputs {
+    pub direct: DirectOutputs,
+    /// Mean square of the reflection effect output.
+    pub reflection_energy: f32,
+    /// Mean square of the reverb effect output.
+    pub reverb_energy: f32,
+}
+
+/// Reflection simulation parameters of the sources and the listener source. Reverb times are
+/// estimated at their actual scale, and the whole impulse response is kept for convolution.
+fn reflections_simulation_parameters() -> audionimbus::ReflectionsSimulationParameters {
+    audionimbus::ReflectionsSimulationParameters::Hybrid {
+        reverb_scale: [1.0; 3],
+        transition_time: 2.0,
+        overlap_percent: 0.25,
+        baked_data_identifier: None,
+    }
+}
+
+/// Mean square of `samples`, or 0 if empty.
+pub fn mean_square(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32
+}
+
+/// Direct path parameters computed by the simulator for a source. Bands are low, mid and high
+/// frequencies.
+#[derive(Clone, Copy, Debug)]
+pub struct DirectOutputs {
+    pub distance_attenuation: f32,
+    pub air_absorption: [f32; 3],
+    pub directivity: f32,
+    /// 1 when the direct path is clear, 0 when fully occluded.
+    pub occlusion: f32,
+    pub transmission: [f32; 3],
+}
+
+impl Default for DirectOutputs {
+    fn default() -> Self {
+        Self {
+            distance_attenuation: 1.0,
+            air_absorption: [1.0; 3],
+            directivity: 1.0,
+            occlusion: 1.0,
+            transmission: [1.0; 3],
+        }
+    }
+}
+
+impl DirectOutputs {
+    /// Overall gain of the direct path at mid frequencies, counting sound transmitted through
+    /// occluders.
+    pub fn gain(&self) -> f32 {
+        let occlusion = self.occlusion + (1.0 - self.occlusion) * self.transmission[1];
+        self.distance_attenuation * self.air_absorption[1] * self.directivity * occlusion
+    }
+
+    pub fn new(params: &audionimbus::DirectEffectParams) -> Self {
+        let transmission = match &params.transmission {
+            Some(audionimbus::Transmission::FrequencyIndependent(equalizer))
+            | Some(audionimbus::Transmission::FrequencyDependent(equalizer)) => equalizer.0,
+            None => [1.0; 3],
+        };
+
+        Self {
+            distance_attenuation: params.distance_attenuation.unwrap_or(1.0),
+            air_absorption: params
+                .air_absorption
+                .as_ref()
+                .map_or([1.0; 3], |equalizer| equalizer.0),
+            directivity: params.directivity.unwrap_or(1.0),
+            occlusion: params.occlusion.unwrap_or(1.0),
+            transmission,
+        }
+    }
+}
+
+/// Marks the entity that hears the audio, such as a camera or the head of a character.
+///
+/// Without one, the first active 3D camera is used as the listener.
+#[derive(Component, Default)]
+#[require(GlobalTransform)]
+pub struct AudioListener;
+
+/// Transform of the listener in the current frame, resolved by [`Plugin::update_listener`].
+/// `None` while there is neither an [`AudioListener`] nor a 3D camera, in which case audio is
+/// paused.
+#[derive(Resource, Default)]
+pub struct Listener {
+    pub transform: Option<GlobalTransform>,
 }
 
 #[derive(Resource)]
 pub struct ListenerSource {
     // Special source used for reverb.
     pub source: audionimbus::Source,
+    /// Mean square of the reverb output of all sources in the latest frame.
+    pub reverb_energy: f32,
+}
+
+/// Time spent by the latest run of [`Plugin::process_frame`].
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct ProcessingTimes {
+    /// Running the direct simulation.
+    pub direct: Duration,
+    /// Running the reflections simulation.
+    pub reflections: Duration,
+    /// Applying the effects to the sources and mixing the frames, all of them when several are
+    /// rendered at once.
+    pub dsp: Duration,
 }
 
-pub struct Plugin;
+#[derive(Default)]
+pub struct Plugin {
+    /// Renders frames into [`AudioOutput::Offline`] instead of playing them on a device, in
+    /// deterministic mode.
+    pub offline: bool,
+    /// Runs the app in [`DeterministicMode`].
+    pub deterministic: bool,
+    /// Sources the simulator can hold, including the listener source, or [`MAX_NUM_SOURCES`] if
+    /// `None`.
+    pub max_num_sources: Option<usize>,
+}
 
 impl Plugin {
-    fn process_frame(
+    /// Paces [`DeterministicMode`] to the output device, leaving the time still while the device
+    /// has more than [`MAX_QUEUED_FRAMES`] frames queued.
+    fn pace_updates(audio: Res<Audio>, mut time_update_strategy: ResMut<TimeUpdateStrategy>) {
+        let AudioOutput::Device(sink) = &audio.output else {
+            return;
+        };
+        let step = if sink.len() > MAX_QUEUED_FRAMES {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(FRAME_SIZE as f64 / SAMPLING_RATE as f64)
+        };
+        *time_update_strategy = TimeUpdateStrategy::ManualDuration(step);
+    }
+
+    pub(crate) fn update_listener(
+        query_listeners: Query<(Entity, &GlobalTransform), With<AudioListener>>,
+        query_cameras: Query<(Entity, &Camera, &GlobalTransform), With<Camera3d>>,
+        mut listener: ResMut<Listener>,
+    ) {
+        let mut listeners = query_listeners.iter();
+        if let Some((entity, transform)) = listeners.next() {
+            if listeners.next().is_some() {
+                warn_once!("Several entities have an AudioListener, using {entity}");
+            }
+            listener.transform = Some(*transform);
+            return;
+        }
+
+        let camera = query_cameras
+            .iter()
+            .filter(|(_, camera, _)| camera.is_active)
+            .min_by_key(|(_, camera, _)| camera.order);
+        listener.transform = match camera {
+            Some((entity, _, transform)) => {
+                warn_once!("No entity has an AudioListener, using camera {entity} as the listener");
+                Some(*transform)
+            }
+            None => {
+                warn_once!(
+                    "No entity has an AudioListener and there is no 3D camera, pausing audio"
+                );
+                None
+            }
+        };
+    }
+
+    /// Frees the voice of sources when they are removed, including when their entity is
+    /// despawned. The simulator is committed once for all of them by [`Self::process_frame`].
+    fn remove_source(
+        remove: On<Remove, AudioSource>,
+        query_audio_sources: Query<&AudioSource>,
+        mut audio: ResMut<Audio>,
+    ) {
+        let Ok(audio_source) = query_audio_sources.get(remove.entity) else {
+            return;
+        };
+        if audio_source.voice.is_real {
+            audio.simulator.remove_source(&audio_source.source);
+            audio.needs_commit = true;
+        }
+    }
+
+    pub(crate) fn process_frame(
         mut commands: Commands,
-        query_character: Single<&GlobalTransform, With<Camera3d>>,
-        mut query_audio_sources: Query<(Entity, &GlobalTransform, &mut AudioSource)>,
+        listener: Res<Listener>,
+        mut query_audio_sources: Query<(
+            Entity,
+            &GlobalTransform,
+            &mut AudioSource,
+            Option<&AudioBus>,
+            Option<&PlaybackSchedule>,
+        )>,
+        mut query_non_spatial_sources: Query<(
+            Entity,
+            &mut NonSpatialAudioSource,
+            Option<&AudioBus>,
+            Option<&PlaybackSchedule>,
+        )>,
         time: Res<Time>,
+        clips: Res<Assets<AudioClip>>,
         mut audio: ResMut<Audio>,
         mut listener_source: ResMut<ListenerSource>,
+        mut meters: ResMut<Meters>,
+        mut master_dynamics: ResMut<MasterDynamics>,
+        mixer: Res<Mixer>,
+        mut audio_clock: ResMut<AudioClock>,
+        mut recorder: ResMut<Recorder>,
+        mut processing_times: ResMut<ProcessingTimes>,
+        deterministic_mode: Option<Res<DeterministicMode>>,
     ) {
         audio.timer.tick(time.delta());
+        let num_frames = if deterministic_mode.is_some() {
+            // Nothing is simulated nor rendered while the time stands still.
+            if time.delta().is_zero() {
+                return;
+            }
+            1
+        } else {
+            audio.timer.times_finished_this_tick()
+        };
 
-        let transform = query_character.into_inner().compute_transform();
+        let Some(listener_transform) = listener.transform else {
+            return;
+        };
+        let transform = listener_transform.compute_transform();
         let listener_position = transform.translation;
 
         let listener_orientation_right = transform.right();
@@ -159,11 +718,7 @@ impl Plugin {
                         algorithm: audionimbus::OcclusionAlgorithm::Raycast,
                     }),
                 }),
-                reflections_simulation: Some(
-                    audionimbus::ReflectionsSimulationParameters::Convolution {
-                        baked_data_identifier: None,
-                    },
-                ),
+                reflections_simulation: Some(reflections_simulation_parameters()),
                 pathing_simulation: None,
             },
         );
@@ -182,114 +737,259 @@ impl Plugin {
                 pathing_visualization_callback: None,
             },
         );
+        let direct_start = Instant::now();
         audio.simulator.run_direct();
+        processing_times.direct = direct_start.elapsed();
+        let reflections_start = Instant::now();
         audio.simulator.run_reflections();
+        processing_times.reflections = reflections_start.elapsed();
+        let dsp_start = Instant::now();
 
         let reverb_simulation_outputs = listener_source
             .source
             .get_outputs(audionimbus::SimulationFlags::REFLECTIONS);
-        let reverb_effect_params = reverb_simulation_outputs.reflections();
+        let mut reverb_effect_params = reverb_simulation_outputs.reflections();
+        reverb_effect_params.reflection_effect_type =
+            audionimbus::ReflectionEffectType::Convolution;
 
-        let times_finished_this_tick = audio.timer.times_finished_this_tick();
-
-        for _ in 0..times_finished_this_tick {
+        for _ in 0..num_frames {
             let mut deinterleaved_container = vec![0.0; FRAME_SIZE * NUM_CHANNELS];
+            // Sum of the ambisonic mixes of the sources, only needed when recording.
+            let mut ambisonic_container = recorder
+                .is_recording()
+                .then(|| vec![0.0; FRAME_SIZE * AMBISONICS_NUM_CHANNELS]);
+            let mut reverb_energy = 0.0;
+            meters.direct.clear();
+            meters.reflections.clear();
+            meters.reverb.clear();
+            meters.clear_buses(&mixer);
 
             // Iterate over each audio source.
-            for (entity, source_global_transform, mut audio_source) in
+            for (entity, source_global_transform, mut audio_source, audio_bus, schedule) in
                 query_audio_sources.iter_mut()
             {
-                let frame = if audio_source.is_repeating {
-                    let frame: Vec<_> = (0..FRAME_SIZE)
-                        .map(|i| {
-                            audio_source.data[(audio_source.position + i) % audio_source.data.len()]
-                        })
-                        .collect();
-
-                    // Advance sample position.
-                    audio_source.position =
-                        (audio_source.position + FRAME_SIZE) % audio_source.data.len();
-
-                    frame
-                } else {
-                    let frame = (0..FRAME_SIZE)
-                        .map(|i| {
-                            let idx = audio_source.position + i;
-                            // If no more samples, fill with silence.
-                            if idx < audio_source.data.len() {
-                                audio_source.data[idx]
-                            } else {
-                                0.0
-                            }
-                        })
-                        .collect();
+                let Some((clip, cue_volume, cue_pitch)) =
+                    audio_source.sound.current(&audio_source.cue_pick)
+                else {
+                    // The cue has not picked a clip yet.
+                    continue;
+                };
+                let Some(clip) = clips.get(clip) else {
+                    // Not loaded yet.
+                    continue;
+                };
+                if let ClipLayout::Ambisonic { .. } = clip.layout {
+                    error!(
+                        "Source {entity} plays an ambisonic clip, which only a \
+                         NonSpatialAudioSource can play as a bed, despawning it"
+                    );
+                    commands.entity(entity).despawn();
+                    continue;
+                }
 
-                    // Advance sample position.
-                    audio_source.position += FRAME_SIZE;
+                let (window, stops) = schedule.map_or((0..FRAME_SIZE, false), |schedule| {
+                    schedule.window(audio_clock.samples)
+                });
+                if stops {
+                    // Despawn audio source, which frees its voice.
+                    commands.entity(entity).despawn();
+                }
+                if window.is_empty() {
+                    // Not started yet, or stopped at the start of the frame.
+                    audio_source.meter.measure(&[]);
+                    continue;
+                }
 
-                    // If there are no more audio samples to play back.
-                    if audio_source.position >= audio_source.data.len() {
-                        // Despawn audio source.
-                        commands.entity(entity).despawn();
+                // Clips picked by cues play once, then repeating sources pick another.
+                let is_repeating = audio_source.is_repeating && !audio_source.sound.is_cue();
+                let pitch = audio_source.pitch * cue_pitch;
+                let audio_source = &mut *audio_source;
+                if !audio_source.voice.is_real {
+                    // Virtual voices are silent but keep their place in the clip.
+                    let finished = advance_position(
+                        clip.num_samples(),
+                        &mut audio_source.position,
+                        playback_step(clip, pitch),
+                        is_repeating,
+                        window.len(),
+                    );
+                    if finished && !stops {
+                        end_clip(
+                            &mut commands,
+                            entity,
+                            &audio_source.sound,
+                            audio_source.is_repeating,
+                            &mut audio_source.position,
+                            &mut audio_source.cue_pick,
+                        );
                     }
+                    audio_source.meter.measure(&[]);
+                    continue;
+                }
 
-                    frame
-                };
+                let (mut channels, finished) = next_frame(
+                    clip,
+                    &mut audio_source.position,
+                    pitch,
+                    is_repeating,
+                    window,
+                );
+                if finished && !stops {
+                    end_clip(
+                        &mut commands,
+                        entity,
+                        &audio_source.sound,
+                        audio_source.is_repeating,
+                        &mut audio_source.position,
+                        &mut audio_source.cue_pick,
+                    );
+                }
+
+                for sample in channels.iter_mut().flatten() {
+                    *sample *= audio_source.volume * cue_volume;
+                }
 
                 let source_position = source_global_transform.translation();
+                let source_right = source_global_transform.right();
+                let source_up = source_global_transform.up();
+                let source_ahead = source_global_transform.forward();
 
+                let occlusion_algorithm = match audio_source.occlusion_radius {
+                    Some(radius) => audionimbus::OcclusionAlgorithm::Volumetric {
+                        radius,
+                        num_occlusion_samples: 16,
+                    },
+                    None => audionimbus::OcclusionAlgorithm::Raycast,
+                };
+
+                // Reflection inputs are set, and outputs read, while the source has reflections or
+                // while they fade out after it lost them.
+                let needs_reflections = audio_source.lod != LodTier::DirectOnly
+                    || audio_source.rendered_lod != LodTier::DirectOnly;
+                let source_simulation_flags = if !needs_reflections {
+                    audionimbus::SimulationFlags::DIRECT
+                } else {
+                    simulation_flags
+                };
                 audio_source.source.set_inputs(
-                    simulation_flags,
+                    source_simulation_flags,
                     audionimbus::SimulationInputs {
                         source: audionimbus::CoordinateSystem {
+                            right: audionimbus::Vector3::new(
+                                source_right.x,
+                                source_right.y,
+                                source_right.z,
+                            ),
+                            up: audionimbus::Vector3::new(source_up.x, source_up.y, source_up.z),
+                            ahead: audionimbus::Vector3::new(
+                                source_ahead.x,
+                                source_ahead.y,
+                                source_ahead.z,
+                            ),
                             origin: audionimbus::Vector3::new(
                                 source_position.x,
                                 source_position.y,
                                 source_position.z,
                             ),
-                            ..Default::default()
                         },
                         direct_simulation: Some(audionimbus::DirectSimulationParameters {
                             distance_attenuation: Some(
                                 audionimbus::DistanceAttenuationModel::Default,
                             ),
                             air_absorption: Some(audionimbus::AirAbsorptionModel::Default),
-                            directivity: Some(audionimbus::Directivity::default()),
+                            directivity: Some(audionimbus::Directivity {
+                                dipole_weight: audio_source.directivity.dipole_weight,
+                                dipole_power: audio_source.directivity.dipole_power,
+                                ..Default::default()
+                            }),
                             occlusion: Some(audionimbus::Occlusion {
                                 transmission: Some(audionimbus::TransmissionParameters {
                                     num_transmission_rays: 8,
                                 }),
-                                algorithm: audionimbus::OcclusionAlgorithm::Raycast,
+                                algorithm: occlusion_algorithm,
                             }),
                         }),
-                        reflections_simulation: Some(
-                            audionimbus::ReflectionsSimulationParameters::Convolution {
-                                baked_data_identifier: None,
-                            },
-                        ),
+                        reflections_simulation: needs_reflections
+                            .then(reflections_simulation_parameters),
                         pathing_simulation: None,
                     },
                 );
 
-                let simulation_outputs = audio_source.source.get_outputs(simulation_flags);
+                let simulation_outputs = audio_source.source.get_outputs(source_simulation_flags);
                 let direct_effect_params = simulation_outputs.direct();
-                let reflection_effect_params = simulation_outputs.reflections();
-
-                let input_buffer = audionimbus::AudioBuffer::try_with_data(&frame).unwrap();
+                audio_source.outputs.direct = DirectOutputs::new(&direct_effect_params);
+                let reflection_effect_params =
+                    needs_reflections.then(|| simulation_outputs.reflections());
 
-                let mut direct_container = vec![0.0; FRAME_SIZE];
-                let direct_buffer =
-                    audionimbus::AudioBuffer::try_with_data(&mut direct_container).unwrap();
-                let _effect_state =
-                    audio
-                        .direct_effect
-                        .apply(&direct_effect_params, &input_buffer, &direct_buffer);
+                // Stereo clips are two emitters spread along the right axis of the source, sharing
+                // its simulation results. Other clips are a single emitter at the source.
+                let emitters = match clip.layout {
+                    ClipLayout::Mono => vec![source_position],
+                    ClipLayout::Stereo => {
+                        let offset = source_right * audio_source.spread / 2.0;
+                        vec![source_position - offset, source_position + offset]
+                    }
+                    ClipLayout::Ambisonic { .. } => {
+                        unreachable!("ambisonic clips are rejected above")
+                    }
+                };
 
-                let direction = source_position - listener_position;
-                let direction = audionimbus::Direction::new(direction.x, direction.y, direction.z);
+                // Reflections and reverb are simulated from the source, so they are fed the
+                // emitters mixed down to mono.
+                let frame = (0..FRAME_SIZE)
+                    .map(|i| {
+                        channels.iter().map(|channel| channel[i]).sum::<f32>()
+                            / channels.len() as f32
+                    })
+                    .collect::<Vec<_>>();
+                let input_buffer = audionimbus::AudioBuffer::try_with_data(&frame).unwrap();
 
                 let mut ambisonics_encode_container =
                     vec![0.0; FRAME_SIZE * AMBISONICS_NUM_CHANNELS];
+                for (channel, emitter_position) in channels.iter().zip(emitters) {
+                    let emitter_input_buffer =
+                        audionimbus::AudioBuffer::try_with_data(channel).unwrap();
+
+                    let mut direct_container = vec![0.0; FRAME_SIZE];
+                    let direct_buffer =
+                        audionimbus::AudioBuffer::try_with_data(&mut direct_container).unwrap();
+                    let _effect_state = audio.direct_effect.apply(
+                        &direct_effect_params,
+                        &emitter_input_buffer,
+                        &direct_buffer,
+                    );
+
+                    let direction = emitter_position - listener_position;
+                    let direction =
+                        audionimbus::Direction::new(direction.x, direction.y, direction.z);
+
+                    let mut emitter_container = vec![0.0; FRAME_SIZE * AMBISONICS_NUM_CHANNELS];
+                    let emitter_buffer = audionimbus::AudioBuffer::try_with_data_and_settings(
+                        &mut emitter_container,
+                        &audionimbus::AudioBufferSettings {
+                            num_channels: Some(AMBISONICS_NUM_CHANNELS),
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+                    let ambisonics_encode_effect_params =
+                        audionimbus::AmbisonicsEncodeEffectParams {
+                            direction,
+                            order: AMBISONICS_ORDER,
+                        };
+                    let _effect_state = audio.ambisonics_encode_effect.apply(
+                        &ambisonics_encode_effect_params,
+                        &direct_buffer,
+                        &emitter_buffer,
+                    );
+
+                    for (output, sample) in ambisonics_encode_container
+                        .iter_mut()
+                        .zip(&emitter_container)
+                    {
+                        *output += sample;
+                    }
+                }
                 let ambisonics_encode_buffer =
                     audionimbus::AudioBuffer::try_with_data_and_settings(
                         &mut ambisonics_encode_container,
@@ -299,17 +999,59 @@ impl Plugin {
                         },
                     )
                     .unwrap();
-                let ambisonics_encode_effect_params = audionimbus::AmbisonicsEncodeEffectParams {
-                    direction,
-                    order: AMBISONICS_ORDER,
-                };
-                let _effect_state = audio.ambisonics_encode_effect.apply(
-                    &ambisonics_encode_effect_params,
-                    &direct_buffer,
-                    &ambisonics_encode_buffer,
-                );
 
+                // Sources without reflections leave the reflection buffer silent. Changing tiers
+                // crossfades from the reflections of the previous tier to those of the new one over
+                // the frame, so that they don't pop.
                 let mut reflection_container = vec![0.0; FRAME_SIZE * AMBISONICS_NUM_CHANNELS];
+                if let Some(mut reflection_effect_params) = reflection_effect_params {
+                    let tier_fades = if audio_source.rendered_lod == audio_source.lod {
+                        vec![(audio_source.lod, (1.0, 1.0))]
+                    } else {
+                        vec![
+                            (audio_source.rendered_lod, (1.0, 0.0)),
+                            (audio_source.lod, (0.0, 1.0)),
+                        ]
+                    };
+                    for (tier, fade) in tier_fades {
+                        let mut tier_container = vec![0.0; FRAME_SIZE * AMBISONICS_NUM_CHANNELS];
+                        let tier_buffer = audionimbus::AudioBuffer::try_with_data_and_settings(
+                            &mut tier_container,
+                            &audionimbus::AudioBufferSettings {
+                                num_channels: Some(AMBISONICS_NUM_CHANNELS),
+                                ..Default::default()
+                            },
+                        )
+                        .unwrap();
+                        match tier {
+                            LodTier::DirectOnly => continue,
+                            LodTier::Parametric => {
+                                reflection_effect_params.reflection_effect_type =
+                                    audionimbus::ReflectionEffectType::Parametric;
+                                let _effect_state = audio.parametric_reflection_effect.apply(
+                                    &reflection_effect_params,
+                                    &input_buffer,
+                                    &tier_buffer,
+                                );
+                            }
+                            LodTier::Convolution => {
+                                reflection_effect_params.reflection_effect_type =
+                                    audionimbus::ReflectionEffectType::Convolution;
+                                let _effect_state = audio.reflection_effect.apply(
+                                    &reflection_effect_params,
+                                    &input_buffer,
+                                    &tier_buffer,
+                                );
+                            }
+                        }
+                        apply_fade(&mut tier_container, fade);
+                        for (output, sample) in reflection_container.iter_mut().zip(&tier_container)
+                        {
+                            *output += sample;
+                        }
+                    }
+                }
+                audio_source.rendered_lod = audio_source.lod;
                 let reflection_buffer = audionimbus::AudioBuffer::try_with_data_and_settings(
                     &mut reflection_container,
                     &audionimbus::AudioBufferSettings {
@@ -318,11 +1060,6 @@ impl Plugin {
                     },
                 )
                 .unwrap();
-                let _effect_state = audio.reflection_effect.apply(
-                    &reflection_effect_params,
-                    &input_buffer,
-                    &reflection_buffer,
-                );
 
                 let mut reverb_container = vec![0.0; FRAME_SIZE * AMBISONICS_NUM_CHANNELS];
                 let reverb_buffer = audionimbus::AudioBuffer::try_with_data_and_settings(
@@ -361,6 +1098,27 @@ impl Plugin {
                     )
                 })
                 .collect::<Vec<_>>();
+
+                // The omnidirectional channel comes first in ACN order.
+                let gain_normalization =
+                    GAIN_FACTOR_DIRECT + GAIN_FACTOR_REFLECTIONS + GAIN_FACTOR_REVERB;
+                meters.direct.add(
+                    &ambisonics_encode_container[..FRAME_SIZE],
+                    GAIN_FACTOR_DIRECT / gain_normalization,
+                );
+                meters.reflections.add(
+                    &reflection_container[..FRAME_SIZE],
+                    GAIN_FACTOR_REFLECTIONS / gain_normalization,
+                );
+                meters.reverb.add(
+                    &reverb_container[..FRAME_SIZE],
+                    GAIN_FACTOR_REVERB / gain_normalization,
+                );
+
+                audio_source.outputs.reflection_energy = mean_square(&reflection_container);
+                audio_source.outputs.reverb_energy = mean_square(&reverb_container);
+                reverb_energy += audio_source.outputs.reverb_energy;
+
                 let mix_buffer = audionimbus::AudioBuffer::try_with_data_and_settings(
                     &mut mix_container,
                     &audionimbus::AudioBufferSettings {
@@ -392,6 +1150,34 @@ impl Plugin {
                     &staging_buffer,
                 );
 
+                // Applied after the effects so that muting a bus also cuts reverb tails.
+                let bus_gain = mixer.effective_gain(audio_bus.copied().unwrap_or_default().0);
+                for sample in &mut staging_container {
+                    *sample *= bus_gain;
+                }
+                let fade = audio_source.voice.advance_fade();
+                apply_fade(&mut staging_container, fade);
+                meters.add_to_buses(
+                    &mixer,
+                    audio_bus.copied().unwrap_or_default().0,
+                    &staging_container,
+                );
+                if let Some(ambisonic_container) = &mut ambisonic_container {
+                    for sample in &mut mix_container {
+                        *sample *= bus_gain;
+                    }
+                    apply_fade(&mut mix_container, fade);
+                    for (output, sample) in ambisonic_container.iter_mut().zip(&mix_container) {
+                        *output += sample;
+                    }
+                }
+                if audio_source.voice.is_real && audio_source.voice.is_faded_out() {
+                    audio.simulator.remove_source(&audio_source.source);
+                    audio_source.voice.is_real = false;
+                    audio.needs_commit = true;
+                }
+                audio_source.meter.measure(&staging_container);
+
                 deinterleaved_container = staging_container
                     .iter()
                     .zip(deinterleaved_container.iter())
@@ -399,6 +1185,141 @@ impl Plugin {
                     .collect();
             }
 
+            for (entity, mut non_spatial_source, audio_bus, schedule) in
+                query_non_spatial_sources.iter_mut()
+            {
+                let Some((clip, cue_volume, cue_pitch)) = non_spatial_source
+                    .sound
+                    .current(&non_spatial_source.cue_pick)
+                else {
+                    // The cue has not picked a clip yet.
+                    continue;
+                };
+                let Some(clip) = clips.get(clip) else {
+                    // Not loaded yet.
+                    continue;
+                };
+
+                let (window, stops) = schedule.map_or((0..FRAME_SIZE, false), |schedule| {
+                    schedule.window(audio_clock.samples)
+                });
+                if stops {
+                    commands.entity(entity).despawn();
+                }
+                if window.is_empty() {
+                    non_spatial_source.meter.measure(&[]);
+                    continue;
+                }
+
+                let is_repeating =
+                    non_spatial_source.is_repeating && !non_spatial_source.sound.is_cue();
+                let pitch = non_spatial_source.pitch * cue_pitch;
+                let non_spatial_source = &mut *non_spatial_source;
+                let (frame, finished) = next_frame(
+                    clip,
+                    &mut non_spatial_source.position,
+                    pitch,
+                    is_repeating,
+                    window,
+                );
+                if finished && !stops {
+                    end_clip(
+                        &mut commands,
+                        entity,
+                        &non_spatial_source.sound,
+                        non_spatial_source.is_repeating,
+                        &mut non_spatial_source.position,
+                        &mut non_spatial_source.cue_pick,
+                    );
+                }
+
+                let gain = non_spatial_source.volume
+                    * cue_volume
+                    * mixer.effective_gain(audio_bus.copied().unwrap_or_default().0);
+                let mut staging_container = vec![0.0; FRAME_SIZE * NUM_CHANNELS];
+                if let ClipLayout::Ambisonic { .. } = clip.layout {
+                    let mut bed_container = ambix_to_n3d(&frame);
+                    let bed_buffer = audionimbus::AudioBuffer::try_with_data_and_settings(
+                        &mut bed_container,
+                        &audionimbus::AudioBufferSettings {
+                            num_channels: Some(AMBISONICS_NUM_CHANNELS),
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+                    let staging_buffer = audionimbus::AudioBuffer::try_with_data_and_settings(
+                        &mut staging_container,
+                        &audionimbus::AudioBufferSettings {
+                            num_channels: Some(NUM_CHANNELS),
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+
+                    let ambisonics_decode_effect_params =
+                        audionimbus::AmbisonicsDecodeEffectParams {
+                            order: AMBISONICS_ORDER,
+                            hrtf: &audio.hrtf,
+                            orientation: listener_orientation,
+                            binaural: false,
+                        };
+                    let _effect_state = audio.ambisonics_decode_effect.apply(
+                        &ambisonics_decode_effect_params,
+                        &bed_buffer,
+                        &staging_buffer,
+                    );
+
+                    for sample in &mut staging_container {
+                        *sample *= gain;
+                    }
+                    if let Some(ambisonic_container) = &mut ambisonic_container {
+                        for (output, sample) in ambisonic_container.iter_mut().zip(&bed_container) {
+                            *output += sample * gain;
+                        }
+                    }
+                } else {
+                    // A mono clip feeds both channels.
+                    let channel_gains = non_spatial_source.channel_gains();
+                    for (channel, (output, channel_gain)) in staging_container
+                        .chunks_exact_mut(FRAME_SIZE)
+                        .zip(channel_gains)
+                        .enumerate()
+                    {
+                        let input = &frame[channel.min(frame.len() - 1)];
+                        for (output, sample) in output.iter_mut().zip(input) {
+                            *output = sample * gain * channel_gain;
+                        }
+                    }
+                }
+                non_spatial_source.meter.measure(&staging_container);
+                meters.add_to_buses(
+                    &mixer,
+                    audio_bus.copied().unwrap_or_default().0,
+                    &staging_container,
+                );
+
+                for (output, sample) in deinterleaved_container.iter_mut().zip(&staging_container) {
+                    *output += sample;
+                }
+            }
+
+            listener_source.reverb_energy = reverb_energy;
+            meters.direct.measure();
+            meters.reflections.measure();
+            meters.reverb.measure();
+            meters.measure_buses();
+
+            // Sources add up without normalization, so the limiter keeps the sum from clipping.
+            // The master is metered before it, to count the samples it kept from clipping.
+            meters.master.measure(&deinterleaved_container);
+            master_dynamics.process(&mut deinterleaved_container);
+            if let Some(ambisonic_container) = &ambisonic_container {
+                if let Err(error) = recorder.write(&deinterleaved_container, ambisonic_container) {
+                    error!("Failed to write recording: {error}");
+                    let _ = recorder.stop();
+                }
+            }
+
             let deinterleaved_buffer = audionimbus::AudioBuffer::try_with_data_and_settings(
                 &mut deinterleaved_container,
                 &audionimbus::AudioBufferSettings {
@@ -409,18 +1330,47 @@ impl Plugin {
             .unwrap();
             let mut interleaved = vec![0.0; FRAME_SIZE * NUM_CHANNELS];
             deinterleaved_buffer.interleave(&audio.context, &mut interleaved);
-            let source = AudioFrame::new(interleaved, 2);
 
-            audio.sink.append(source);
+            match &mut audio.output {
+                AudioOutput::Device(sink) => sink.append(AudioFrame::new(interleaved, 2)),
+                AudioOutput::Offline(samples) => samples.extend(interleaved),
+            }
+            audio_clock.samples += FRAME_SIZE as u64;
+        }
+
+        if audio.needs_commit {
+            audio.simulator.commit();
+            audio.needs_commit = false;
         }
+        processing_times.dsp = dsp_start.elapsed();
     }
 }
 
 impl bevy::app::Plugin for Plugin {
     fn build(&self, app: &mut App) {
-        let (stream, stream_handle) = OutputStream::try_default().unwrap();
-        let sink = Sink::try_new(&stream_handle).unwrap();
-        app.insert_non_send_resource(stream);
+        let output = if self.offline {
+            AudioOutput::Offline(Vec::new())
+        } else {
+            let (stream, stream_handle) = OutputStream::try_default().unwrap();
+            app.insert_non_send_resource(stream);
+            AudioOutput::Device(Sink::try_new(&stream_handle).unwrap())
+        };
+        if self.offline || self.deterministic {
+            let frame_duration = Duration::from_secs_f64(FRAME_SIZE as f64 / SAMPLING_RATE as f64);
+            app.insert_resource(DeterministicMode)
+                .insert_resource(TimeUpdateStrategy::ManualDuration(frame_duration));
+            if !self.offline {
+                app.add_systems(First, Self::pace_updates.before(TimeSystems));
+            }
+        }
+
+        app.init_asset::<AudioClip>()
+            .init_asset_loader::<RawClipLoader>()
+            .init_asset_loader::<WavClipLoader>()
+            .init_resource::<Meters>()
+            .init_resource::<MasterDynamics>()
+            .init_resource::<Mixer>()
+            .init_resource::<Recorder>();
 
         let context =
             audionimbus::Context::try_new(&audionimbus::ContextSettings::default()).unwrap();
@@ -430,56 +1380,11 @@ impl bevy::app::Plugin for Plugin {
             sampling_rate: SAMPLING_RATE,
         };
 
-        let mut scene =
+        // The scene is filled by the levels.
+        let scene =
             audionimbus::Scene::try_new(&context, &audionimbus::SceneSettings::default()).unwrap();
 
-        let walls = audionimbus::StaticMesh::try_new(
-            &scene,
-            &audionimbus::StaticMeshSettings {
-                vertices: &[
-                    // Floor
-                    audionimbus::Point::new(-2.0, 0.0, -2.0),
-                    audionimbus::Point::new(2.0, 0.0, -2.0),
-                    audionimbus::Point::new(2.0, 0.0, 2.0),
-                    audionimbus::Point::new(-2.0, 0.0, 2.0),
-                    // Ceiling
-                    audionimbus::Point::new(-2.0, 4.0, -2.0),
-                    audionimbus::Point::new(2.0, 4.0, -2.0),
-                    audionimbus::Point::new(2.0, 4.0, 2.0),
-                    audionimbus::Point::new(-2.0, 4.0, 2.0),
-                    // Back wall
-                    audionimbus::Point::new(-2.0, 0.0, -2.0),
-                    audionimbus::Point::new(2.0, 0.0, -2.0),
-                    audionimbus::Point::new(2.0, 4.0, -2.0),
-                    audionimbus::Point::new(-2.0, 4.0, -2.0),
-                    // Left wall
-                    audionimbus::Point::new(-2.0, 0.0, -2.0),
-                    audionimbus::Point::new(-2.0, 0.0, 2.0),
-                    audionimbus::Point::new(-2.0, 4.0, 2.0),
-                    audionimbus::Point::new(-2.0, 4.0, -2.0),
-                ],
-                triangles: &[
-                    // Floor
-                    audionimbus::Triangle::new(0, 1, 2),
-                    audionimbus::Triangle::new(0, 2, 3),
-                    // Ceiling
-                    audionimbus::Triangle::new(4, 6, 5),
-                    audionimbus::Triangle::new(4, 7, 6),
-                    // Back wall
-                    audionimbus::Triangle::new(8, 9, 10),
-                    audionimbus::Triangle::new(8, 10, 11),
-                    // Left wall
-                    audionimbus::Triangle::new(12, 14, 13),
-                    audionimbus::Triangle::new(12, 15, 14),
-                ],
-                material_indices: &[0, 0, 0, 0, 0, 0, 0, 0],
-                materials: &[audionimbus::Material::WOOD],
-            },
-        )
-        .unwrap();
-        scene.add_static_mesh(&walls);
-        scene.commit();
-
+        let max_num_sources = self.max_num_sources.unwrap_or(MAX_NUM_SOURCES);
         let mut simulator = audionimbus::Simulator::builder(
             audionimbus::SceneParams::Default,
             SAMPLING_RATE,
@@ -488,12 +1393,14 @@ impl bevy::app::Plugin for Plugin {
         .with_direct(audionimbus::DirectSimulationSettings {
             max_num_occlusion_samples: 16,
         })
-        .with_reflections(audionimbus::ReflectionsSimulationSettings::Convolution {
+        // Hybrid simulation estimates reverb times along with the impulse responses, so that
+        // reflections can be rendered by convolution or parametrically.
+        .with_reflections(audionimbus::ReflectionsSimulationSettings::Hybrid {
             max_num_rays: 2048,
             num_diffuse_samples: 8,
             max_duration: 2.0,
             max_order: AMBISONICS_ORDER,
-            max_num_sources: 8,
+            max_num_sources,
             num_threads: 1,
         })
         .try_build(&context)
@@ -510,6 +1417,7 @@ impl bevy::app::Plugin for Plugin {
         simulator.add_source(&listener_source);
         app.insert_resource(ListenerSource {
             source: listener_source,
+            reverb_energy: 0.0,
         });
         simulator.commit();
 
@@ -540,6 +1448,16 @@ impl bevy::app::Plugin for Plugin {
         )
         .unwrap();
 
+        let parametric_reflection_effect = audionimbus::ReflectionEffect::try_new(
+            &context,
+            &settings,
+            &audionimbus::ReflectionEffectSettings::Parametric {
+                impulse_response_size: 2 * SAMPLING_RATE,
+                num_channels: AMBISONICS_NUM_CHANNELS,
+            },
+        )
+        .unwrap();
+
         let reverb_effect = audionimbus::ReflectionEffect::try_new(
             &context,
             &settings,
@@ -573,20 +1491,34 @@ impl bevy::app::Plugin for Plugin {
         app.insert_resource(Audio {
             context,
             scene,
+            static_meshes: StaticMeshes::default(),
+            instanced_meshes: InstancedMeshes::default(),
             simulator,
+            max_num_sources,
+            needs_commit: false,
             hrtf,
             direct_effect,
             reflection_effect,
+            parametric_reflection_effect,
             reverb_effect,
             ambisonics_encode_effect,
             ambisonics_decode_effect,
-            sink,
+            output,
             timer: Timer::new(
                 Duration::from_secs_f32(FRAME_SIZE as f32 / SAMPLING_RATE as f32),
                 TimerMode::Repeating,
             ),
         });
 
-        app.add_systems(PostUpdate, Self::process_frame);
+        app.init_resource::<Listener>()
+            .init_resource::<AudioClock>()
+            .init_resource::<ProcessingTimes>()
+            .add_observer(Self::remove_source)
+            .add_systems(
+                PostUpdate,
+                (Self::update_listener, Self::process_frame)
+                    .chain()
+                    .after(TransformSystems::Propagate),
+            );
     }
 }
@@ -0,0 +1,159 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use thiserror::Error;
+
+use crate::audio::SAMPLING_RATE;
+
+/// Audio samples played back by an [`AudioSource`](crate::audio::AudioSource).
+#[derive(Asset, TypePath, Debug)]
+pub struct AudioClip {
+    /// Samples of each channel, all of the same length.
+    pub channels: Vec<Vec<audionimbus::Sample>>,
+    pub layout: ClipLayout,
+    /// Samples per second, resampled to [`SAMPLING_RATE`] on playback.
+    pub sampling_rate: u32,
+}
+
+impl AudioClip {
+    /// Number of samples in each channel.
+    pub fn num_samples(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+}
+
+/// How the channels of a clip are meant to be played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClipLayout {
+    Mono,
+    /// Left and right channels.
+    Stereo,
+    /// Soundfield in the AmbiX convention: ACN channel order and SN3D normalization.
+    Ambisonic {
+        order: usize,
+    },
+}
+
+/// Loads headerless mono clips made of little-endian `f32` samples at
+/// [`SAMPLING_RATE`](crate::audio::SAMPLING_RATE).
+#[derive(Default)]
+pub struct RawClipLoader;
+
+impl AssetLoader for RawClipLoader {
+    type Asset = AudioClip;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        let samples = bytes
+            .chunks_exact(4) // f32 is 4 bytes
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+
+        Ok(AudioClip {
+            channels: vec![samples],
+            layout: ClipLayout::Mono,
+            sampling_rate: SAMPLING_RATE as u32,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["raw"]
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum WavClipLoaderError {
+    #[error("could not read clip: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not decode WAV: {0}")]
+    Wav(#[from] hound::Error),
+    #[error("unsupported number of channels: {0}")]
+    UnsupportedChannels(u16),
+}
+
+/// Loads mono and stereo `.wav` clips, and AmbiX soundfields from `.ambix.wav` files, at any
+/// sampling rate.
+#[derive(Default)]
+pub struct WavClipLoader;
+
+impl AssetLoader for WavClipLoader {
+    type Asset = AudioClip;
+    type Settings = ();
+    type Error = WavClipLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        let wav_reader = hound::WavReader::new(std::io::Cursor::new(bytes))?;
+        let spec = wav_reader.spec();
+
+        let num_channels = spec.channels as usize;
+        let is_ambix = load_context
+            .path()
+            .to_string_lossy()
+            .ends_with(".ambix.wav");
+        let layout = match num_channels {
+            _ if is_ambix => {
+                // (order + 1)^2 channels.
+                let order = (num_channels as f32).sqrt() as usize - 1;
+                if (order + 1).pow(2) != num_channels {
+                    return Err(WavClipLoaderError::UnsupportedChannels(spec.channels));
+                }
+                ClipLayout::Ambisonic { order }
+            }
+            1 => ClipLayout::Mono,
+            2 => ClipLayout::Stereo,
+            _ => return Err(WavClipLoaderError::UnsupportedChannels(spec.channels)),
+        };
+
+        let interleaved = match spec.sample_format {
+            hound::SampleFormat::Float => wav_reader
+                .into_samples::<f32>()
+                .collect::<Result<Vec<_>, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                wav_reader
+                    .into_samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 * scale))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+
+        let channels = (0..num_channels)
+            .map(|channel| {
+                interleaved
+                    .iter()
+                    .skip(channel)
+                    .step_by(num_channels)
+                    .copied()
+                    .collect()
+            })
+            .collect();
+
+        Ok(AudioClip {
+            channels,
+            layout,
+            sampling_rate: spec.sample_rate,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["wav", "ambix.wav"]
+    }
+}
@@ -0,0 +1,77 @@
+//! Sample-accurate timing of playback.
+//!
+//! The [`AudioClock`] counts the samples rendered so far. Sources with a [`PlaybackSchedule`]
+//! start and stop at exact sample times on this clock, even in the middle of a frame, so that
+//! rhythmic and layered sounds line up regardless of the frame rate:
+//!
+//! ```ignore
+//! let beat = clock.after_secs(0.5);
+//! commands.spawn((kick, PlaybackSchedule::starting_at(beat)));
+//! commands.spawn((hi_hat, PlaybackSchedule::starting_at(beat + clock_samples(0.25))));
+//! ```
+
+use std::ops::Range;
+
+use bevy::prelude::*;
+
+use crate::audio::{FRAME_SIZE, SAMPLING_RATE};
+
+/// Converts a duration in seconds to a number of samples.
+pub fn clock_samples(secs: f64) -> u64 {
+    (secs * SAMPLING_RATE as f64).round() as u64
+}
+
+/// Number of samples rendered per channel since the app started.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct AudioClock {
+    /// Sample time at which the next frame starts.
+    pub samples: u64,
+}
+
+impl AudioClock {
+    pub fn secs(&self) -> f64 {
+        self.samples as f64 / SAMPLING_RATE as f64
+    }
+
+    /// Sample time `secs` seconds after the start of the next frame.
+    pub fn after_secs(&self, secs: f64) -> u64 {
+        self.samples + clock_samples(secs)
+    }
+}
+
+/// Start and stop times of an [`AudioSource`](crate::audio::AudioSource) or
+/// [`NonSpatialAudioSource`](crate::audio::NonSpatialAudioSource), on the [`AudioClock`].
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct PlaybackSchedule {
+    /// Sample time at which the clip starts, or `None` to start with the next frame. Sources
+    /// scheduled in the past start with the next frame.
+    pub start: Option<u64>,
+    /// Sample time at which the source is cut and despawned, or `None` to play the clip to the end.
+    pub stop: Option<u64>,
+}
+
+impl PlaybackSchedule {
+    pub fn starting_at(start: u64) -> Self {
+        Self {
+            start: Some(start),
+            stop: None,
+        }
+    }
+
+    pub fn with_stop(mut self, stop: u64) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Samples of the frame starting at `frame_start` during which the source plays, and whether
+    /// the source stops within the frame.
+    pub fn window(&self, frame_start: u64) -> (Range<usize>, bool) {
+        let frame_end = frame_start + FRAME_SIZE as u64;
+        let offset = |time: u64| (time.clamp(frame_start, frame_end) - frame_start) as usize;
+        let begin = self.start.map_or(0, offset);
+        match self.stop {
+            Some(stop) if stop <= frame_end => (begin..offset(stop).max(begin), true),
+            _ => (begin..FRAME_SIZE, false),
+        }
+    }
+}
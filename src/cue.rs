@@ -0,0 +1,326 @@
+//! Sound cues: clips played with variation, described by `.cue.ron` files.
+//!
+//! ```ron
+//! (
+//!     clips: ["footstep_1.wav", "footstep_2.wav", "footstep_3.wav"],
+//!     selection: Shuffle,
+//!     pitch: (0.95, 1.05),
+//!     volume: (0.8, 1.0),
+//!     cooldown: 0.1,
+//! )
+//! ```
+//!
+//! A cue is played wherever a clip is, through [`Sound`]. Each time a source starts, the cue picks
+//! one of its clips along with a pitch and volume in its ranges. Repeating sources pick a new clip
+//! every time the previous one ends instead of looping it.
+
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    audio::{AudioSource, DeterministicMode, NonSpatialAudioSource},
+    clip::AudioClip,
+};
+
+/// A clip, or a cue picking clips.
+#[derive(Clone, Debug)]
+pub enum Sound {
+    Clip(Handle<AudioClip>),
+    Cue(Handle<SoundCue>),
+}
+
+impl Sound {
+    /// Loads a cue from a `.cue.ron` path, or a clip from any other path.
+    pub fn load(asset_server: &AssetServer, path: &str) -> Self {
+        if path.ends_with(".cue.ron") {
+            Self::Cue(asset_server.load(path.to_owned()))
+        } else {
+            Self::Clip(asset_server.load(path.to_owned()))
+        }
+    }
+
+    /// Clip to play, with the volume and pitch multipliers picked by the cue. `None` while a cue
+    /// has not picked a clip yet.
+    pub fn current(&self, pick: &Option<CuePick>) -> Option<(AssetId<AudioClip>, f32, f32)> {
+        match (self, pick) {
+            (Self::Clip(clip), _) => Some((clip.id(), 1.0, 1.0)),
+            (Self::Cue(_), Some(pick)) => Some((pick.clip.id(), pick.volume, pick.pitch)),
+            (Self::Cue(_), None) => None,
+        }
+    }
+
+    pub fn is_cue(&self) -> bool {
+        matches!(self, Self::Cue(_))
+    }
+}
+
+impl From<Handle<AudioClip>> for Sound {
+    fn from(clip: Handle<AudioClip>) -> Self {
+        Self::Clip(clip)
+    }
+}
+
+impl From<Handle<SoundCue>> for Sound {
+    fn from(cue: Handle<SoundCue>) -> Self {
+        Self::Cue(cue)
+    }
+}
+
+/// Clip picked by a cue, with its randomized volume and pitch.
+#[derive(Clone, Debug)]
+pub struct CuePick {
+    pub clip: Handle<AudioClip>,
+    pub volume: f32,
+    pub pitch: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum Selection {
+    /// Any clip, independently of the previous ones.
+    #[default]
+    Random,
+    /// Every clip once in a random order, then again in another order.
+    Shuffle,
+    /// Clips in the order they are listed.
+    Sequential,
+}
+
+#[derive(Asset, TypePath, Debug)]
+pub struct SoundCue {
+    #[dependency]
+    pub clips: Vec<Handle<AudioClip>>,
+    pub selection: Selection,
+    /// Range of the playback rate.
+    pub pitch: (f32, f32),
+    /// Range of the linear gain.
+    pub volume: (f32, f32),
+    /// Minimum time between two picks, in seconds. Sources starting earlier are dropped, or wait
+    /// if they repeat.
+    pub cooldown: f32,
+}
+
+#[derive(Deserialize)]
+struct SoundCueDescriptor {
+    clips: Vec<String>,
+    #[serde(default)]
+    selection: Selection,
+    #[serde(default = "SoundCueDescriptor::default_range")]
+    pitch: (f32, f32),
+    #[serde(default = "SoundCueDescriptor::default_range")]
+    volume: (f32, f32),
+    #[serde(default)]
+    cooldown: f32,
+}
+
+impl SoundCueDescriptor {
+    fn default_range() -> (f32, f32) {
+        (1.0, 1.0)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SoundCueLoaderError {
+    #[error("could not read cue: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse cue: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("cue has no clips")]
+    NoClips,
+}
+
+#[derive(Default)]
+pub struct SoundCueLoader;
+
+impl AssetLoader for SoundCueLoader {
+    type Asset = SoundCue;
+    type Settings = ();
+    type Error = SoundCueLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let descriptor: SoundCueDescriptor = ron::de::from_bytes(&bytes)?;
+        if descriptor.clips.is_empty() {
+            return Err(SoundCueLoaderError::NoClips);
+        }
+
+        Ok(SoundCue {
+            clips: descriptor
+                .clips
+                .into_iter()
+                .map(|path| load_context.load(path))
+                .collect(),
+            selection: descriptor.selection,
+            pitch: descriptor.pitch,
+            volume: descriptor.volume,
+            cooldown: descriptor.cooldown,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["cue.ron"]
+    }
+}
+
+/// Random numbers of the cues, seeded with [`DeterministicMode::SEED`] in deterministic mode.
+#[derive(Resource)]
+pub struct CueRng(pub SmallRng);
+
+impl Default for CueRng {
+    fn default() -> Self {
+        Self(SmallRng::from_os_rng())
+    }
+}
+
+/// Playback history of a cue.
+#[derive(Default)]
+struct CueState {
+    /// Clip indices left to play in the current shuffle, last first.
+    shuffled: Vec<usize>,
+    last_index: Option<usize>,
+    /// Elapsed time of the last pick, in seconds.
+    last_pick: Option<f32>,
+}
+
+impl CueState {
+    /// Picks the next clip, or `None` during the cooldown.
+    fn pick(&mut self, cue: &SoundCue, now: f32, rng: &mut SmallRng) -> Option<CuePick> {
+        if self
+            .last_pick
+            .is_some_and(|last_pick| now - last_pick < cue.cooldown)
+        {
+            return None;
+        }
+
+        let num_clips = cue.clips.len();
+        let index = match cue.selection {
+            Selection::Random => rng.random_range(0..num_clips),
+            Selection::Shuffle => {
+                if self.shuffled.is_empty() {
+                    self.shuffled = (0..num_clips).collect();
+                    self.shuffled.shuffle(rng);
+                    // Don't play the same clip twice in a row across shuffles.
+                    if num_clips > 1 && self.shuffled.last() == self.last_index.as_ref() {
+                        self.shuffled.swap(0, num_clips - 1);
+                    }
+                }
+                self.shuffled.pop().unwrap()
+            }
+            Selection::Sequential => self.last_index.map_or(0, |index| (index + 1) % num_clips),
+        };
+        self.last_index = Some(index);
+        self.last_pick = Some(now);
+
+        let random_in = |rng: &mut SmallRng, (min, max): (f32, f32)| {
+            if min < max {
+                rng.random_range(min..=max)
+            } else {
+                min
+            }
+        };
+        Some(CuePick {
+            clip: cue.clips[index].clone(),
+            volume: random_in(rng, cue.volume),
+            pitch: random_in(rng, cue.pitch),
+        })
+    }
+}
+
+#[derive(Resource, Default)]
+struct CueStates(HashMap<AssetId<SoundCue>, CueState>);
+
+pub struct Plugin;
+
+impl Plugin {
+    fn seed_rng(deterministic_mode: Option<Res<DeterministicMode>>, mut rng: ResMut<CueRng>) {
+        if deterministic_mode.is_some() {
+            rng.0 = SmallRng::seed_from_u64(DeterministicMode::SEED);
+        }
+    }
+
+    fn pick_clips(
+        mut commands: Commands,
+        time: Res<Time>,
+        cues: Res<Assets<SoundCue>>,
+        mut cue_states: ResMut<CueStates>,
+        mut rng: ResMut<CueRng>,
+        mut query_audio_sources: Query<(Entity, &mut AudioSource)>,
+        mut query_non_spatial_sources: Query<(Entity, &mut NonSpatialAudioSource)>,
+        deterministic_mode: Option<Res<DeterministicMode>>,
+    ) {
+        // In deterministic mode, cues pick their clips once the time advances, so that the draws
+        // don't depend on how long the cues took to load.
+        if deterministic_mode.is_some() && time.delta().is_zero() {
+            return;
+        }
+        let now = time.elapsed_secs();
+        let mut pick =
+            |entity: Entity, sound: &Sound, cue_pick: &mut Option<CuePick>, is_repeating: bool| {
+                let Sound::Cue(cue_handle) = sound else {
+                    return;
+                };
+                if cue_pick.is_some() {
+                    return;
+                }
+                let Some(cue) = cues.get(cue_handle) else {
+                    // Not loaded yet.
+                    return;
+                };
+
+                *cue_pick = cue_states
+                    .0
+                    .entry(cue_handle.id())
+                    .or_default()
+                    .pick(cue, now, &mut rng.0);
+                if cue_pick.is_none() && !is_repeating {
+                    // Dropped during the cooldown.
+                    commands.entity(entity).despawn();
+                }
+            };
+
+        for (entity, mut audio_source) in query_audio_sources.iter_mut() {
+            let audio_source = &mut *audio_source;
+            pick(
+                entity,
+                &audio_source.sound,
+                &mut audio_source.cue_pick,
+                audio_source.is_repeating,
+            );
+        }
+        for (entity, mut non_spatial_source) in query_non_spatial_sources.iter_mut() {
+            let non_spatial_source = &mut *non_spatial_source;
+            pick(
+                entity,
+                &non_spatial_source.sound,
+                &mut non_spatial_source.cue_pick,
+                non_spatial_source.is_repeating,
+            );
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<SoundCue>()
+            .init_asset_loader::<SoundCueLoader>()
+            .init_resource::<CueRng>()
+            .init_resource::<CueStates>()
+            .add_systems(Startup, Self::seed_rng)
+            .add_systems(
+                PostUpdate,
+                Self::pick_clips.before(crate::audio::Plugin::process_frame),
+            );
+    }
+}
@@ -0,0 +1,159 @@
+//! Overlay showing what the simulator sees, toggled with F1.
+//!
+//! - Acoustic triangles, colored by material.
+//! - Sources, with their volumetric occlusion radius and directivity pattern.
+//! - The direct path from the listener to each source, from green (clear) to red (occluded).
+//! - Hits of rays cast from the listener into the acoustic scene.
+
+use bevy::prelude::*;
+
+use crate::{
+    audio::{Audio, AudioSource, Listener},
+    geometry::{self, AcousticMesh},
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F1;
+/// Rays cast from the listener to visualize the geometry around it.
+const NUM_PROBE_RAYS: usize = 128;
+const MAX_RAY_DISTANCE: f32 = 100.0;
+/// Length of the directivity pattern along the axis of the source.
+const DIRECTIVITY_SCALE: f32 = 0.5;
+
+#[derive(Resource, Default)]
+pub struct DebugGizmos {
+    pub enabled: bool,
+}
+
+fn material_color(material: &audionimbus::Material) -> Color {
+    match geometry::NAMED_MATERIALS
+        .iter()
+        .position(|(_, named_material)| geometry::same_material(material, named_material))
+    {
+        Some(index) => Color::hsl(
+            360.0 * index as f32 / geometry::NAMED_MATERIALS.len() as f32,
+            0.8,
+            0.5,
+        ),
+        None => Color::WHITE,
+    }
+}
+
+fn occlusion_color(occlusion: f32) -> Color {
+    Color::srgb(1.0 - occlusion, occlusion, 0.0)
+}
+
+/// Points evenly distributed on the unit sphere.
+fn fibonacci_sphere(num_points: usize) -> impl Iterator<Item = Vec3> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+    (0..num_points).map(move |i| {
+        let y = 1.0 - 2.0 * (i as f32 + 0.5) / num_points as f32;
+        let radius = (1.0 - y * y).sqrt();
+        let theta = golden_angle * i as f32;
+        Vec3::new(radius * theta.cos(), y, radius * theta.sin())
+    })
+}
+
+fn draw_mesh(gizmos: &mut Gizmos, mesh: &AcousticMesh, transform: Mat4) {
+    for (triangle, material_index) in mesh.triangles.iter().zip(&mesh.material_indices) {
+        let [a, b, c] =
+            triangle.map(|index| transform.transform_point3(mesh.vertices[index as usize]));
+        gizmos.linestrip(
+            [a, b, c, a],
+            material_color(&mesh.materials[*material_index]),
+        );
+    }
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn toggle(key_input: Res<ButtonInput<KeyCode>>, mut debug_gizmos: ResMut<DebugGizmos>) {
+        if key_input.just_pressed(TOGGLE_KEY) {
+            debug_gizmos.enabled = !debug_gizmos.enabled;
+        }
+    }
+
+    fn draw(
+        mut gizmos: Gizmos,
+        debug_gizmos: Res<DebugGizmos>,
+        audio: Res<Audio>,
+        listener: Res<Listener>,
+        query_audio_sources: Query<(&GlobalTransform, &AudioSource)>,
+    ) {
+        if !debug_gizmos.enabled {
+            return;
+        }
+
+        for static_mesh in audio.static_meshes.meshes.values() {
+            draw_mesh(&mut gizmos, &static_mesh.mesh, Mat4::IDENTITY);
+        }
+        for instance in audio.instanced_meshes.0.values() {
+            draw_mesh(&mut gizmos, &instance.mesh, instance.transform);
+        }
+
+        let listener_position = listener.transform.map(|transform| transform.translation());
+
+        for (transform, audio_source) in query_audio_sources.iter() {
+            let source_position = transform.translation();
+            let isometry = Isometry3d::new(source_position, transform.rotation());
+
+            gizmos.sphere(
+                isometry,
+                audio_source.occlusion_radius.unwrap_or(0.1),
+                Color::srgb(0.2, 0.4, 1.0),
+            );
+
+            // Directivity pattern in the horizontal and vertical planes of the source.
+            let forward = transform.forward();
+            for side in [transform.right(), transform.up()] {
+                let points = (0..=64).map(|i| {
+                    let angle = std::f32::consts::TAU * i as f32 / 64.0;
+                    let direction = forward * angle.cos() + side * angle.sin();
+                    source_position
+                        + direction * DIRECTIVITY_SCALE * audio_source.directivity.gain(angle)
+                });
+                gizmos.linestrip(points, Color::srgb(0.2, 0.8, 1.0));
+            }
+
+            if let Some(listener_position) = listener_position {
+                gizmos.line(
+                    listener_position,
+                    source_position,
+                    occlusion_color(audio_source.outputs.direct.occlusion),
+                );
+
+                if let Ok(direction) = Dir3::new(source_position - listener_position) {
+                    let ray = Ray3d::new(listener_position, direction);
+                    let distance = listener_position.distance(source_position);
+                    if let Some(hit) = audio.raycast(ray, distance) {
+                        gizmos.sphere(
+                            Isometry3d::from_translation(hit.point),
+                            0.05,
+                            Color::srgb(1.0, 0.0, 0.0),
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(listener_position) = listener_position {
+            for direction in fibonacci_sphere(NUM_PROBE_RAYS) {
+                let ray = Ray3d::new(listener_position, Dir3::new_unchecked(direction));
+                if let Some(hit) = audio.raycast(ray, MAX_RAY_DISTANCE) {
+                    gizmos.sphere(
+                        Isometry3d::from_translation(hit.point),
+                        0.03,
+                        material_color(&hit.material),
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugGizmos>()
+            .add_systems(Update, (Self::toggle, Self::draw).chain());
+    }
+}
@@ -0,0 +1,303 @@
+//! Dynamics processing on the master output.
+//!
+//! Processors work in place on planar buffers, channel after channel, as used by the
+//! deinterleaved mix in [`process_frame`](crate::audio::Plugin::process_frame). Channels are
+//! linked: the loudest one sets the gain of all of them, which keeps the stereo image steady.
+//! Nothing allocates after construction.
+
+use bevy::prelude::*;
+
+use crate::audio::{NUM_CHANNELS, SAMPLING_RATE};
+
+/// Delay of the limiter, giving it time to pull the gain down before a peak reaches the output.
+const LIMITER_LOOKAHEAD: f32 = 0.005;
+const LIMITER_CEILING_DB: f32 = -1.0;
+const LIMITER_RELEASE_TIME: f32 = 0.1;
+
+fn from_decibels(decibels: f32) -> f32 {
+    10.0_f32.powf(decibels / 20.0)
+}
+
+/// One-pole smoothing coefficient reaching about 63% of a step after `time` seconds.
+fn smoothing_coefficient(time: f32) -> f32 {
+    if time <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / (time * SAMPLING_RATE as f32)).exp()
+    }
+}
+
+/// Largest absolute sample across the channels of the `index`th sample of a planar buffer.
+fn linked_peak(samples: &[f32], num_channels: usize, index: usize) -> f32 {
+    let len = samples.len() / num_channels;
+    (0..num_channels)
+        .map(|channel| samples[channel * len + index].abs())
+        .fold(0.0, f32::max)
+}
+
+/// Look-ahead brickwall limiter. Output samples never exceed `ceiling`, at the cost of
+/// delaying the signal by the look-ahead time.
+#[derive(Debug)]
+pub struct Limiter {
+    /// Largest absolute output sample, as a linear amplitude.
+    pub ceiling: f32,
+    /// Time for the gain to recover after a peak, in seconds.
+    pub release_time: f32,
+    num_channels: usize,
+    lookahead: usize,
+    /// Input samples not yet output, per channel.
+    delay: Vec<Vec<f32>>,
+    /// Gains required by the latest input samples, over the look-ahead window.
+    required_gains: Vec<f32>,
+    /// Released gains over the look-ahead window, averaged to smooth the attack.
+    released_gains: Vec<f32>,
+    released_gains_sum: f64,
+    released_gain: f32,
+    position: usize,
+    gain: f32,
+}
+
+impl Limiter {
+    pub fn new(num_channels: usize, lookahead: usize, ceiling: f32, release_time: f32) -> Self {
+        Self {
+            ceiling,
+            release_time,
+            num_channels,
+            lookahead,
+            delay: vec![vec![0.0; lookahead.max(1)]; num_channels],
+            required_gains: vec![1.0; lookahead + 1],
+            released_gains: vec![1.0; lookahead + 1],
+            released_gains_sum: (lookahead + 1) as f64,
+            released_gain: 1.0,
+            position: 0,
+            gain: 1.0,
+        }
+    }
+
+    /// Gain applied to the latest output sample.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Delay of the output behind the input, in samples.
+    pub fn latency(&self) -> usize {
+        self.lookahead
+    }
+
+    /// Limits a planar buffer in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let len = samples.len() / self.num_channels;
+        let release = smoothing_coefficient(self.release_time);
+        let window = self.lookahead + 1;
+
+        for index in 0..len {
+            let peak = linked_peak(samples, self.num_channels, index);
+            let required_gain = if peak > self.ceiling {
+                self.ceiling / peak
+            } else {
+                1.0
+            };
+
+            // The smallest gain required within the window covers the sample leaving the delay
+            // line, and averaging gains that each cover it ramps the gain down without
+            // overshooting.
+            let slot = self.position % window;
+            self.required_gains[slot] = required_gain;
+            let target_gain = self.required_gains.iter().copied().fold(1.0, f32::min);
+            self.released_gain = if target_gain < self.released_gain {
+                target_gain
+            } else {
+                target_gain + release * (self.released_gain - target_gain)
+            };
+            self.released_gains_sum += (self.released_gain - self.released_gains[slot]) as f64;
+            self.released_gains[slot] = self.released_gain;
+            self.gain = (self.released_gains_sum / window as f64) as f32;
+
+            if self.lookahead == 0 {
+                for channel in 0..self.num_channels {
+                    samples[channel * len + index] *= self.gain;
+                }
+            } else {
+                let delay_slot = self.position % self.lookahead;
+                for (channel, delay) in self.delay.iter_mut().enumerate() {
+                    let sample = &mut samples[channel * len + index];
+                    let delayed = std::mem::replace(&mut delay[delay_slot], *sample);
+                    *sample = delayed * self.gain;
+                }
+            }
+
+            self.position = (self.position + 1) % (window * self.lookahead.max(1));
+        }
+    }
+}
+
+/// Feed-forward compressor reducing the level above `threshold_db` by `ratio`.
+#[derive(Clone, Copy, Debug)]
+pub struct Compressor {
+    pub threshold_db: f32,
+    /// Input decibels above the threshold per output decibel above it.
+    pub ratio: f32,
+    /// Time for the gain reduction to engage, in seconds.
+    pub attack_time: f32,
+    /// Time for the gain reduction to recover, in seconds.
+    pub release_time: f32,
+    envelope_db: f32,
+}
+
+impl Compressor {
+    pub fn new(threshold_db: f32, ratio: f32, attack_time: f32, release_time: f32) -> Self {
+        Self {
+            threshold_db,
+            ratio,
+            attack_time,
+            release_time,
+            envelope_db: 0.0,
+        }
+    }
+
+    /// Gain currently applied, as a linear amplitude.
+    pub fn gain(&self) -> f32 {
+        from_decibels(-self.envelope_db)
+    }
+
+    /// Compresses a planar buffer with `num_channels` channels in place.
+    pub fn process(&mut self, samples: &mut [f32], num_channels: usize) {
+        let len = samples.len() / num_channels;
+        let attack = smoothing_coefficient(self.attack_time);
+        let release = smoothing_coefficient(self.release_time);
+
+        for index in 0..len {
+            let peak = linked_peak(samples, num_channels, index);
+            let level_db = 20.0 * peak.max(1e-6).log10();
+            let overshoot_db = (level_db - self.threshold_db).max(0.0);
+            let gain_reduction_db = overshoot_db * (1.0 - 1.0 / self.ratio.max(1.0));
+
+            let coefficient = if gain_reduction_db > self.envelope_db {
+                attack
+            } else {
+                release
+            };
+            self.envelope_db =
+                gain_reduction_db + coefficient * (self.envelope_db - gain_reduction_db);
+
+            let gain = self.gain();
+            for channel in 0..num_channels {
+                samples[channel * len + index] *= gain;
+            }
+        }
+    }
+}
+
+/// Processors applied to the master output, compressor first.
+#[derive(Resource, Debug)]
+pub struct MasterDynamics {
+    /// Compressor shaping the mix, disabled by default.
+    pub compressor: Option<Compressor>,
+    /// Limiter keeping the output from clipping.
+    pub limiter: Limiter,
+}
+
+impl MasterDynamics {
+    /// Processes the planar master output in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        if let Some(compressor) = &mut self.compressor {
+            compressor.process(samples, NUM_CHANNELS);
+        }
+        self.limiter.process(samples);
+    }
+}
+
+impl Default for MasterDynamics {
+    fn default() -> Self {
+        Self {
+            compressor: None,
+            limiter: Limiter::new(
+                NUM_CHANNELS,
+                (LIMITER_LOOKAHEAD * SAMPLING_RATE as f32) as usize,
+                from_decibels(LIMITER_CEILING_DB),
+                LIMITER_RELEASE_TIME,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{audio::FRAME_SIZE, metering::decibels};
+
+    fn lookahead() -> usize {
+        (LIMITER_LOOKAHEAD * SAMPLING_RATE as f32) as usize
+    }
+
+    /// Planar buffer of a frame, with `sample(index)` in every channel.
+    fn frame(sample: impl Fn(usize) -> f32) -> Vec<f32> {
+        (0..NUM_CHANNELS)
+            .flat_map(|_| (0..FRAME_SIZE).map(&sample))
+            .collect()
+    }
+
+    #[test]
+    fn limiter_never_exceeds_ceiling() {
+        let mut dynamics = MasterDynamics::default();
+        let ceiling = dynamics.limiter.ceiling;
+
+        // Silence, then a full-scale burst, then a burst twice as loud, then silence again.
+        for amplitude in [0.0, 1.0, 1.0, 2.0, 2.0, 2.0, 0.0, 0.0] {
+            let mut samples = frame(|index| {
+                let phase = std::f32::consts::TAU * 1000.0 * index as f32 / SAMPLING_RATE as f32;
+                amplitude * phase.sin().signum()
+            });
+            dynamics.process(&mut samples);
+            for sample in samples {
+                assert!(
+                    sample.abs() <= ceiling * (1.0 + 1e-5),
+                    "{sample} exceeds the ceiling {ceiling}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn limiter_latency_is_lookahead() {
+        let mut dynamics = MasterDynamics::default();
+        let mut samples = frame(|index| if index == 0 { 0.5 } else { 0.0 });
+        dynamics.process(&mut samples);
+
+        for channel in samples.chunks(FRAME_SIZE) {
+            let delay = channel.iter().position(|sample| *sample != 0.0);
+            assert_eq!(delay, Some(lookahead()));
+            assert_eq!(
+                channel[lookahead()],
+                0.5,
+                "quiet samples are not attenuated"
+            );
+        }
+    }
+
+    #[test]
+    fn compressor_attack_and_release() {
+        let (attack_time, release_time) = (0.01, 0.1);
+        let mut compressor = Compressor::new(-20.0, 4.0, attack_time, release_time);
+        // A full-scale input is 20 dB over the threshold, reduced to 5 dB over it.
+        let gain_reduction_db = 15.0;
+        let samples_of = |time: f32| (time * SAMPLING_RATE as f32).round() as usize;
+
+        // One-pole smoothing reaches 1 - 1/e of a step after its time constant.
+        process(&mut compressor, 1.0, samples_of(attack_time));
+        let expected_db = -gain_reduction_db * (1.0 - (-1.0_f32).exp());
+        assert!((decibels(compressor.gain()) - expected_db).abs() < 0.1);
+
+        process(&mut compressor, 1.0, samples_of(1.0));
+        assert!((decibels(compressor.gain()) + gain_reduction_db).abs() < 0.1);
+
+        process(&mut compressor, 0.0, samples_of(release_time));
+        let expected_db = -gain_reduction_db * (-1.0_f32).exp();
+        assert!((decibels(compressor.gain()) - expected_db).abs() < 0.1);
+    }
+
+    fn process(compressor: &mut Compressor, amplitude: f32, num_samples: usize) {
+        let mut samples = vec![amplitude; num_samples * NUM_CHANNELS];
+        compressor.process(&mut samples, NUM_CHANNELS);
+    }
+}
@@ -0,0 +1,462 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    path::Path,
+};
+
+use bevy::{
+    mesh::{PrimitiveTopology, VertexAttributeValues},
+    prelude::*,
+    transform::TransformSystems,
+};
+
+use crate::audio::Audio;
+
+/// Triangle geometry handed to Steam Audio, in world (Bevy) coordinates.
+#[derive(Clone, Debug)]
+pub struct AcousticMesh {
+    pub vertices: Vec<Vec3>,
+    pub triangles: Vec<[i32; 3]>,
+    pub material_indices: Vec<usize>, // One per triangle.
+    pub materials: Vec<audionimbus::Material>,
+}
+
+impl AcousticMesh {
+    /// Planar quad split into two triangles along its `0-2` diagonal.
+    pub fn quad(vertices: [Vec3; 4], material: audionimbus::Material) -> Self {
+        Self {
+            vertices: vertices.to_vec(),
+            triangles: vec![[0, 1, 2], [0, 2, 3]],
+            material_indices: vec![0, 0],
+            materials: vec![material],
+        }
+    }
+
+    /// Axis-aligned box centered on the origin, with every face using `material`.
+    pub fn cuboid(half_size: Vec3, material: audionimbus::Material) -> Self {
+        let Vec3 { x, y, z } = half_size;
+        let vertices = vec![
+            Vec3::new(-x, -y, -z),
+            Vec3::new(x, -y, -z),
+            Vec3::new(x, y, -z),
+            Vec3::new(-x, y, -z),
+            Vec3::new(-x, -y, z),
+            Vec3::new(x, -y, z),
+            Vec3::new(x, y, z),
+            Vec3::new(-x, y, z),
+        ];
+        let triangles = vec![
+            // Back
+            [0, 2, 1],
+            [0, 3, 2],
+            // Front
+            [4, 5, 6],
+            [4, 6, 7],
+            // Bottom
+            [0, 1, 5],
+            [0, 5, 4],
+            // Top
+            [3, 6, 2],
+            [3, 7, 6],
+            // Left
+            [0, 4, 7],
+            [0, 7, 3],
+            // Right
+            [1, 2, 6],
+            [1, 6, 5],
+        ];
+
+        Self {
+            material_indices: vec![0; triangles.len()],
+            vertices,
+            triangles,
+            materials: vec![material],
+        }
+    }
+
+    /// Builds the acoustic counterpart of a triangle list [`Mesh`], with its vertices transformed
+    /// by `transform`. Returns `None` for other topologies or meshes without positions.
+    pub fn from_mesh(
+        mesh: &Mesh,
+        transform: &GlobalTransform,
+        material: audionimbus::Material,
+    ) -> Option<Self> {
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            return None;
+        }
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return None;
+        };
+
+        let indices: Vec<i32> = match mesh.indices() {
+            Some(indices) => indices.iter().map(|index| index as i32).collect(),
+            None => (0..positions.len() as i32).collect(),
+        };
+        let triangles: Vec<[i32; 3]> = indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect();
+
+        Some(Self {
+            vertices: positions
+                .iter()
+                .map(|position| transform.transform_point(Vec3::from_array(*position)))
+                .collect(),
+            material_indices: vec![0; triangles.len()],
+            triangles,
+            materials: vec![material],
+        })
+    }
+
+    /// Closest intersection of `ray` with the mesh placed by `transform`, within `max_distance`.
+    pub fn raycast(&self, transform: Mat4, ray: Ray3d, max_distance: f32) -> Option<RayHit> {
+        let mut closest_hit: Option<RayHit> = None;
+
+        for (triangle, material_index) in self.triangles.iter().zip(&self.material_indices) {
+            let [a, b, c] =
+                triangle.map(|index| transform.transform_point3(self.vertices[index as usize]));
+
+            // Möller-Trumbore intersection.
+            let edge_ab = b - a;
+            let edge_ac = c - a;
+            let p = ray.direction.cross(edge_ac);
+            let determinant = edge_ab.dot(p);
+            if determinant.abs() < f32::EPSILON {
+                continue; // Parallel to the triangle.
+            }
+            let inverse_determinant = 1.0 / determinant;
+            let t_vector = ray.origin - a;
+            let u = t_vector.dot(p) * inverse_determinant;
+            if !(0.0..=1.0).contains(&u) {
+                continue;
+            }
+            let q = t_vector.cross(edge_ab);
+            let v = ray.direction.dot(q) * inverse_determinant;
+            if v < 0.0 || u + v > 1.0 {
+                continue;
+            }
+            let distance = edge_ac.dot(q) * inverse_determinant;
+            if distance <= 0.0 || distance > max_distance {
+                continue;
+            }
+
+            if closest_hit
+                .as_ref()
+                .is_none_or(|closest_hit| distance < closest_hit.distance)
+            {
+                closest_hit = Some(RayHit {
+                    distance,
+                    point: ray.get_point(distance),
+                    material: self.materials[*material_index],
+                });
+            }
+        }
+
+        closest_hit
+    }
+
+    pub fn try_to_static_mesh(
+        &self,
+        scene: &audionimbus::Scene,
+    ) -> Result<audionimbus::StaticMesh, audionimbus::SteamAudioError> {
+        audionimbus::StaticMesh::try_new(
+            scene,
+            &audionimbus::StaticMeshSettings {
+                vertices: &self
+                    .vertices
+                    .iter()
+                    .map(|vertex| audionimbus::Point::new(vertex.x, vertex.y, vertex.z))
+                    .collect::<Vec<_>>(),
+                triangles: &self
+                    .triangles
+                    .iter()
+                    .map(|[a, b, c]| audionimbus::Triangle::new(*a, *b, *c))
+                    .collect::<Vec<_>>(),
+                material_indices: &self.material_indices,
+                materials: &self.materials,
+            },
+        )
+    }
+}
+
+/// Intersection of a ray with acoustic geometry.
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    pub distance: f32,
+    pub point: Vec3,
+    pub material: audionimbus::Material,
+}
+
+/// Steam Audio's predefined materials, used to name materials in exports.
+pub const NAMED_MATERIALS: [(&str, audionimbus::Material); 11] = [
+    ("generic", audionimbus::Material::GENERIC),
+    ("brick", audionimbus::Material::BRICK),
+    ("concrete", audionimbus::Material::CONCRETE),
+    ("ceramic", audionimbus::Material::CERAMIC),
+    ("gravel", audionimbus::Material::GRAVEL),
+    ("carpet", audionimbus::Material::CARPET),
+    ("glass", audionimbus::Material::GLASS),
+    ("plaster", audionimbus::Material::PLASTER),
+    ("wood", audionimbus::Material::WOOD),
+    ("metal", audionimbus::Material::METAL),
+    ("rock", audionimbus::Material::ROCK),
+];
+
+pub fn same_material(a: &audionimbus::Material, b: &audionimbus::Material) -> bool {
+    a.absorption == b.absorption && a.scattering == b.scattering && a.transmission == b.transmission
+}
+
+/// Material of the [`NAMED_MATERIALS`] called `name`.
+pub fn named_material(name: &str) -> Option<audionimbus::Material> {
+    NAMED_MATERIALS
+        .iter()
+        .find(|(material_name, _)| *material_name == name)
+        .map(|(_, material)| *material)
+}
+
+/// Name of `material` if it is one of the [`NAMED_MATERIALS`].
+pub fn material_name(material: &audionimbus::Material) -> Option<&'static str> {
+    NAMED_MATERIALS
+        .iter()
+        .find(|(_, named_material)| same_material(material, named_material))
+        .map(|(name, _)| *name)
+}
+
+/// Identifies a static mesh added through [`Audio::add_static_mesh`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StaticMeshId(u32);
+
+impl StaticMeshId {
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+/// Steam Audio object backing a static [`AcousticMesh`].
+pub struct StaticAcousticMesh {
+    pub mesh: AcousticMesh,
+    pub static_mesh: audionimbus::StaticMesh,
+}
+
+/// Keeps track of the static meshes added to [`Audio::scene`].
+#[derive(Default)]
+pub struct StaticMeshes {
+    pub meshes: BTreeMap<StaticMeshId, StaticAcousticMesh>,
+    next_id: u32,
+}
+
+impl StaticMeshes {
+    pub fn insert(&mut self, mesh: StaticAcousticMesh) -> StaticMeshId {
+        let id = StaticMeshId(self.next_id);
+        self.next_id += 1;
+        self.meshes.insert(id, mesh);
+        id
+    }
+}
+
+/// Writes `meshes` to a Wavefront OBJ file at `path`, along with a sibling MTL file holding one
+/// material per distinct acoustic material.
+///
+/// Each mesh is transformed to world space and becomes its own OBJ object. Faces are grouped by
+/// acoustic material, so material indices can be checked visually in any OBJ viewer.
+pub fn write_obj<'a>(
+    path: &Path,
+    meshes: impl IntoIterator<Item = (String, &'a AcousticMesh, Mat4)>,
+) -> std::io::Result<()> {
+    let mtl_path = path.with_extension("mtl");
+    let mut obj = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut materials: Vec<audionimbus::Material> = Vec::new();
+
+    writeln!(obj, "# Acoustic scene exported by audionimbus-demo")?;
+    if let Some(mtl_file_name) = mtl_path.file_name() {
+        writeln!(obj, "mtllib {}", mtl_file_name.to_string_lossy())?;
+    }
+
+    let mut vertex_offset = 1; // OBJ indices are 1-based.
+    for (name, mesh, transform) in meshes {
+        writeln!(obj, "o {name}")?;
+        for vertex in &mesh.vertices {
+            let vertex = transform.transform_point3(*vertex);
+            writeln!(obj, "v {} {} {}", vertex.x, vertex.y, vertex.z)?;
+        }
+
+        let mut current_material = None;
+        for (triangle, material_index) in mesh.triangles.iter().zip(&mesh.material_indices) {
+            let material = &mesh.materials[*material_index];
+            let material_id = match materials.iter().position(|m| same_material(m, material)) {
+                Some(material_id) => material_id,
+                None => {
+                    materials.push(*material);
+                    materials.len() - 1
+                }
+            };
+            if current_material != Some(material_id) {
+                writeln!(obj, "usemtl {}", mtl_material_name(material_id, material))?;
+                current_material = Some(material_id);
+            }
+
+            let [a, b, c] = triangle.map(|index| index as usize + vertex_offset);
+            writeln!(obj, "f {a} {b} {c}")?;
+        }
+
+        vertex_offset += mesh.vertices.len();
+    }
+    obj.flush()?;
+
+    let mut mtl = std::io::BufWriter::new(std::fs::File::create(&mtl_path)?);
+    for (material_id, material) in materials.iter().enumerate() {
+        writeln!(mtl, "newmtl {}", mtl_material_name(material_id, material))?;
+        writeln!(
+            mtl,
+            "# absorption {:?} scattering {} transmission {:?}",
+            material.absorption, material.scattering, material.transmission
+        )?;
+        // Shade by mid-frequency reflectivity, so absorbent materials appear darker.
+        let reflectivity = 1.0 - material.absorption[1];
+        writeln!(mtl, "Kd {reflectivity} {reflectivity} {reflectivity}")?;
+        writeln!(mtl, "d {}", 1.0 - material.transmission[1])?;
+        writeln!(mtl)?;
+    }
+    mtl.flush()
+}
+
+fn mtl_material_name(material_id: usize, material: &audionimbus::Material) -> String {
+    match material_name(material) {
+        Some(name) => format!("{name}_{material_id}"),
+        None => format!("material_{material_id}"),
+    }
+}
+
+/// Acoustic geometry that follows the [`GlobalTransform`] of its entity.
+///
+/// The mesh is expressed in the entity's local space. It is placed in its own sub-scene and
+/// instanced into [`Audio::scene`], so moving the entity only updates the instance transform
+/// instead of rebuilding the geometry.
+#[derive(Component, Debug)]
+#[require(GlobalTransform)]
+pub struct DynamicAcousticMesh {
+    pub mesh: AcousticMesh,
+}
+
+/// Steam Audio objects backing a [`DynamicAcousticMesh`].
+pub struct InstancedAcousticMesh {
+    pub mesh: AcousticMesh,
+    pub sub_scene: audionimbus::Scene,
+    pub instanced_mesh: audionimbus::InstancedMesh,
+    pub transform: Mat4,
+}
+
+/// Keeps track of the instanced meshes added to [`Audio::scene`].
+#[derive(Default)]
+pub struct InstancedMeshes(pub HashMap<Entity, InstancedAcousticMesh>);
+
+fn to_matrix(transform: Mat4) -> audionimbus::Matrix<f32, 4, 4> {
+    // Steam Audio matrices are row-major, glam matrices are column-major.
+    audionimbus::Matrix::new(transform.transpose().to_cols_array_2d())
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn export_scene(key_input: Res<ButtonInput<KeyCode>>, audio: Res<Audio>) {
+        if !key_input.just_pressed(KeyCode::F9) {
+            return;
+        }
+
+        let path = Path::new("acoustic_scene.obj");
+        match audio.export_obj(path) {
+            Ok(()) => info!("Exported acoustic scene to {}", path.display()),
+            Err(error) => error!("Failed to export acoustic scene: {error}"),
+        }
+    }
+
+    fn sync_dynamic_meshes(
+        query_added: Query<
+            (Entity, &DynamicAcousticMesh, &GlobalTransform),
+            Added<DynamicAcousticMesh>,
+        >,
+        query_moved: Query<
+            (Entity, &GlobalTransform),
+            (With<DynamicAcousticMesh>, Changed<GlobalTransform>),
+        >,
+        mut removed: RemovedComponents<DynamicAcousticMesh>,
+        mut audio: ResMut<Audio>,
+    ) {
+        let audio = &mut *audio;
+        let mut needs_commit = false;
+
+        for entity in removed.read() {
+            if let Some(instance) = audio.instanced_meshes.0.remove(&entity) {
+                audio.scene.remove_instanced_mesh(&instance.instanced_mesh);
+                needs_commit = true;
+            }
+        }
+
+        for (entity, dynamic_mesh, global_transform) in query_added.iter() {
+            let transform = Mat4::from(global_transform.affine());
+
+            let mut sub_scene =
+                audionimbus::Scene::try_new(&audio.context, &audionimbus::SceneSettings::default())
+                    .unwrap();
+            let static_mesh = dynamic_mesh.mesh.try_to_static_mesh(&sub_scene).unwrap();
+            sub_scene.add_static_mesh(&static_mesh);
+            sub_scene.commit();
+
+            let instanced_mesh = audionimbus::InstancedMesh::try_new(
+                &audio.scene,
+                &audionimbus::InstancedMeshSettings {
+                    sub_scene: &sub_scene,
+                    transform: to_matrix(transform),
+                },
+            )
+            .unwrap();
+            audio.scene.add_instanced_mesh(&instanced_mesh);
+
+            audio.instanced_meshes.0.insert(
+                entity,
+                InstancedAcousticMesh {
+                    mesh: dynamic_mesh.mesh.clone(),
+                    sub_scene,
+                    instanced_mesh,
+                    transform,
+                },
+            );
+            needs_commit = true;
+        }
+
+        for (entity, global_transform) in query_moved.iter() {
+            let Some(instance) = audio.instanced_meshes.0.get_mut(&entity) else {
+                continue;
+            };
+
+            let transform = Mat4::from(global_transform.affine());
+            if transform == instance.transform {
+                continue;
+            }
+
+            instance
+                .instanced_mesh
+                .update_transform(&audio.scene, &to_matrix(transform));
+            instance.transform = transform;
+            needs_commit = true;
+        }
+
+        if needs_commit {
+            audio.scene.commit();
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, Self::export_scene);
+        app.add_systems(
+            PostUpdate,
+            Self::sync_dynamic_meshes
+                .after(TransformSystems::Propagate)
+                .before(crate::audio::Plugin::process_frame),
+        );
+    }
+}
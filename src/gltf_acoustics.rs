@@ -0,0 +1,164 @@
+//! Acoustic geometry built from glTF scenes.
+//!
+//! glTF is Y up and right-handed like Bevy and the acoustic scene, and Blender's glTF exporter
+//! converts from Blender's Z up convention on export. Primitives therefore only need their
+//! [`GlobalTransform`] applied, unlike the Blender coordinates typed by hand in older levels.
+
+use std::collections::HashMap;
+
+use bevy::{
+    gltf::{GltfExtras, GltfMaterialExtras, GltfMaterialName, GltfMeshExtras},
+    prelude::*,
+    transform::TransformSystems,
+};
+use serde::Deserialize;
+
+use crate::{
+    audio::Audio,
+    geometry::{self, AcousticMesh, StaticMeshId},
+};
+
+/// Builds acoustic geometry for every mesh primitive spawned under this entity, typically a
+/// [`SceneRoot`] of a glTF scene.
+#[derive(Component)]
+#[require(Transform, Visibility)]
+pub struct GltfAcoustics {
+    /// Acoustic materials keyed by glTF material name.
+    pub materials: HashMap<String, audionimbus::Material>,
+    /// Acoustic material of primitives whose glTF material is missing from `materials`.
+    pub default_material: audionimbus::Material,
+    /// Static meshes built so far, to be removed along with the scene.
+    pub static_meshes: Vec<StaticMeshId>,
+}
+
+/// Marks primitives whose mesh was not loaded yet when they were spawned, to build their acoustic
+/// geometry once it is.
+#[derive(Component)]
+struct PendingAcousticMesh;
+
+/// Extras recognized on glTF nodes, meshes and materials.
+///
+/// `{"acoustic": false}` excludes the primitive from the acoustic scene, and
+/// `{"acoustic_material": "brick"}` overrides its material with one of
+/// [`geometry::NAMED_MATERIALS`]. Extras closest to the primitive take precedence.
+#[derive(Default, Deserialize)]
+struct AcousticExtras {
+    acoustic: Option<bool>,
+    acoustic_material: Option<String>,
+}
+
+impl AcousticExtras {
+    fn parse(extras: &str) -> Self {
+        serde_json::from_str(extras).unwrap_or_default()
+    }
+
+    fn or(self, other: Self) -> Self {
+        Self {
+            acoustic: self.acoustic.or(other.acoustic),
+            acoustic_material: self.acoustic_material.or(other.acoustic_material),
+        }
+    }
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn build_acoustic_meshes(
+        mut commands: Commands,
+        query_primitives: Query<
+            (
+                Entity,
+                &Mesh3d,
+                &GlobalTransform,
+                Option<&GltfMaterialName>,
+                Has<PendingAcousticMesh>,
+            ),
+            Or<(Added<Mesh3d>, With<PendingAcousticMesh>)>,
+        >,
+        query_parents: Query<&ChildOf>,
+        query_extras: Query<(
+            Option<&GltfExtras>,
+            Option<&GltfMeshExtras>,
+            Option<&GltfMaterialExtras>,
+        )>,
+        mut query_gltf_acoustics: Query<&mut GltfAcoustics>,
+        meshes: Res<Assets<Mesh>>,
+        mut audio: ResMut<Audio>,
+    ) {
+        let mut needs_commit = false;
+
+        for (entity, mesh, global_transform, material_name, is_pending) in query_primitives.iter() {
+            let mut extras = AcousticExtras::default();
+            let mut gltf_acoustics_entity = None;
+            for ancestor in std::iter::once(entity).chain(query_parents.iter_ancestors(entity)) {
+                if query_gltf_acoustics.contains(ancestor) {
+                    gltf_acoustics_entity = Some(ancestor);
+                    break;
+                }
+                if let Ok((node_extras, mesh_extras, material_extras)) = query_extras.get(ancestor)
+                {
+                    for value in [
+                        material_extras.map(|extras| &extras.value),
+                        mesh_extras.map(|extras| &extras.value),
+                        node_extras.map(|extras| &extras.value),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    {
+                        extras = extras.or(AcousticExtras::parse(value));
+                    }
+                }
+            }
+
+            let Some(gltf_acoustics_entity) = gltf_acoustics_entity else {
+                continue;
+            };
+            if extras.acoustic == Some(false) {
+                continue;
+            }
+            let Some(mesh) = meshes.get(&mesh.0) else {
+                // Tried again every frame until the mesh is loaded.
+                commands.entity(entity).try_insert(PendingAcousticMesh);
+                continue;
+            };
+            if is_pending {
+                commands.entity(entity).try_remove::<PendingAcousticMesh>();
+            }
+
+            let mut gltf_acoustics = query_gltf_acoustics.get_mut(gltf_acoustics_entity).unwrap();
+            let material = extras
+                .acoustic_material
+                .as_deref()
+                .and_then(geometry::named_material)
+                .or_else(|| {
+                    material_name
+                        .and_then(|material_name| gltf_acoustics.materials.get(&material_name.0))
+                        .copied()
+                })
+                .unwrap_or(gltf_acoustics.default_material);
+
+            let Some(acoustic_mesh) = AcousticMesh::from_mesh(mesh, global_transform, material)
+            else {
+                continue;
+            };
+            let static_mesh = audio.add_static_mesh(acoustic_mesh);
+            gltf_acoustics.static_meshes.push(static_mesh);
+            needs_commit = true;
+        }
+
+        if needs_commit {
+            audio.scene.commit();
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            Self::build_acoustic_meshes
+                .after(TransformSystems::Propagate)
+                .before(crate::audio::Plugin::process_frame),
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! Panel listing the live simulation outputs of every source, toggled with F2.
+//!
+//! Reflections and reverb are shown as the levels of their effect outputs in the latest frame.
+
+use std::fmt::Write as _;
+
+use bevy::prelude::*;
+
+use crate::{
+    audio::{AudioSource, ListenerSource, SAMPLING_RATE},
+    clip::AudioClip,
+    metering::decibels,
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F2;
+
+/// Root node of the inspector panel.
+#[derive(Component)]
+struct Inspector;
+
+/// Text of the inspector panel.
+#[derive(Component)]
+struct InspectorText;
+
+fn energy_decibels(mean_square: f32) -> f32 {
+    10.0 * mean_square.max(1e-12).log10()
+}
+
+fn format_bands(bands: [f32; 3]) -> String {
+    format!(
+        "{:.1} / {:.1} / {:.1} dB",
+        decibels(bands[0]),
+        decibels(bands[1]),
+        decibels(bands[2])
+    )
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn setup(mut commands: Commands) {
+        commands
+            .spawn((
+                Inspector,
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(12.0),
+                    left: Val::Px(12.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+                Visibility::Hidden,
+            ))
+            .with_child((
+                InspectorText,
+                Text::default(),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+            ));
+    }
+
+    fn toggle(
+        key_input: Res<ButtonInput<KeyCode>>,
+        mut query_inspector: Query<&mut Visibility, With<Inspector>>,
+    ) {
+        if !key_input.just_pressed(TOGGLE_KEY) {
+            return;
+        }
+        for mut visibility in query_inspector.iter_mut() {
+            *visibility = match *visibility {
+                Visibility::Hidden => Visibility::Visible,
+                _ => Visibility::Hidden,
+            };
+        }
+    }
+
+    fn update(
+        query_inspector: Query<&Visibility, With<Inspector>>,
+        mut query_text: Query<&mut Text, With<InspectorText>>,
+        query_audio_sources: Query<(Entity, Option<&Name>, &AudioSource)>,
+        listener_source: Res<ListenerSource>,
+        clips: Res<Assets<AudioClip>>,
+    ) {
+        if query_inspector
+            .iter()
+            .all(|visibility| *visibility == Visibility::Hidden)
+        {
+            return;
+        }
+
+        let mut report = String::new();
+        for (entity, name, audio_source) in query_audio_sources.iter() {
+            let outputs = &audio_source.outputs;
+            let clip = audio_source
+                .sound
+                .current(&audio_source.cue_pick)
+                .and_then(|(clip, _, _)| clips.get(clip));
+            let sampling_rate = clip.map_or(SAMPLING_RATE as f32, |clip| clip.sampling_rate as f32);
+            let duration = clip.map_or(0.0, |clip| clip.num_samples() as f32 / sampling_rate);
+
+            let _ = writeln!(
+                report,
+                "{}",
+                name.map_or_else(|| entity.to_string(), |name| name.to_string())
+            );
+            let _ = writeln!(
+                report,
+                "  playback        {:.2} / {:.2} s{}",
+                audio_source.position as f32 / sampling_rate,
+                duration,
+                if audio_source.is_repeating {
+                    " (repeating)"
+                } else {
+                    ""
+                }
+            );
+            let _ = writeln!(
+                report,
+                "  voice           {} (priority {})",
+                if audio_source.voice.is_real {
+                    "real"
+                } else {
+                    "virtual"
+                },
+                audio_source.priority
+            );
+            let _ = writeln!(report, "  reflections LOD {:?}", audio_source.lod);
+            let _ = writeln!(
+                report,
+                "  volume          {:.1} dB",
+                decibels(audio_source.volume)
+            );
+            let _ = writeln!(
+                report,
+                "  distance att.   {:.1} dB",
+                decibels(outputs.direct.distance_attenuation)
+            );
+            let _ = writeln!(
+                report,
+                "  air absorption  {}",
+                format_bands(outputs.direct.air_absorption)
+            );
+            let _ = writeln!(
+                report,
+                "  directivity     {:.1} dB",
+                decibels(outputs.direct.directivity)
+            );
+            let _ = writeln!(report, "  occlusion       {:.2}", outputs.direct.occlusion);
+            let _ = writeln!(
+                report,
+                "  transmission    {}",
+                format_bands(outputs.direct.transmission)
+            );
+            let _ = writeln!(
+                report,
+                "  reflection out  {:.1} dB",
+                energy_decibels(outputs.reflection_energy)
+            );
+            let _ = writeln!(
+                report,
+                "  reverb out      {:.1} dB",
+                energy_decibels(outputs.reverb_energy)
+            );
+        }
+        // The reverb of the listener source is rendered by convolution, so it is summarized by the
+        // level of its output.
+        let _ = writeln!(report, "Listener reverb (convolution)");
+        let _ = writeln!(
+            report,
+            "  output, summed  {:.1} dB",
+            energy_decibels(listener_source.reverb_energy)
+        );
+
+        for mut text in query_text.iter_mut() {
+            text.0.clone_from(&report);
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, Self::setup)
+            .add_systems(Update, (Self::toggle, Self::update).chain());
+    }
+}
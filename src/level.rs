@@ -0,0 +1,700 @@
+//! Levels described by `.level.ron` files.
+//!
+//! Coordinates are in Bevy's world space (Y up), which is also the space used by the acoustic
+//! scene, so no axis conversion happens when building geometry.
+
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext, RenderAssetUsages},
+    mesh::{Indices, PrimitiveTopology},
+    prelude::*,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{
+    audio::{self, Audio},
+    camera_controller::CameraController,
+    cue::Sound,
+    geometry::{self, AcousticMesh, StaticMeshId},
+    gltf_acoustics::GltfAcoustics,
+    mixer::{AudioBus, Mixer},
+};
+
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct Level {
+    pub camera: CameraSpawn,
+    pub ambient_light: f32,
+    /// Acoustic materials in addition to [`geometry::NAMED_MATERIALS`].
+    #[serde(default)]
+    pub materials: HashMap<String, AcousticMaterial>,
+    pub surfaces: Vec<Surface>,
+    #[serde(default)]
+    pub sources: Vec<SoundSource>,
+    #[serde(default)]
+    pub lights: Vec<Light>,
+    #[serde(default)]
+    pub sliding_doors: Vec<SlidingDoorDescriptor>,
+    #[serde(default)]
+    pub gltf_scenes: Vec<GltfScene>,
+}
+
+impl Level {
+    /// Looks up `name` in the level's materials, then in Steam Audio's predefined ones.
+    pub fn material(&self, name: &str) -> Option<audionimbus::Material> {
+        if let Some(material) = self.materials.get(name) {
+            return Some(audionimbus::Material {
+                absorption: material.absorption,
+                scattering: material.scattering,
+                transmission: material.transmission,
+            });
+        }
+
+        geometry::named_material(name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CameraSpawn {
+    pub position: [f32; 3],
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct AcousticMaterial {
+    pub absorption: [f32; 3], // Low, mid, high frequencies.
+    pub scattering: f32,
+    pub transmission: [f32; 3], // Low, mid, high frequencies.
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Surface {
+    pub name: String,
+    pub shape: Shape,
+    pub material: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum Shape {
+    Quad {
+        vertices: [[f32; 3]; 4],
+        normal: [f32; 3],
+    },
+    Mesh {
+        vertices: Vec<[f32; 3]>,
+        triangles: Vec<[u32; 3]>,
+    },
+}
+
+impl Shape {
+    pub fn visual_mesh(&self) -> Mesh {
+        match self {
+            Shape::Quad { vertices, normal } => Mesh::new(
+                PrimitiveTopology::TriangleList,
+                RenderAssetUsages::default(),
+            )
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices.to_vec())
+            .with_inserted_indices(Indices::U32(vec![0, 3, 1, 1, 3, 2]))
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![*normal; 4]),
+            Shape::Mesh {
+                vertices,
+                triangles,
+            } => Mesh::new(
+                PrimitiveTopology::TriangleList,
+                RenderAssetUsages::default(),
+            )
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices.clone())
+            .with_inserted_indices(Indices::U32(triangles.concat()))
+            .with_duplicated_vertices()
+            .with_computed_flat_normals(),
+        }
+    }
+
+    pub fn acoustic_mesh(&self, material: audionimbus::Material) -> AcousticMesh {
+        match self {
+            Shape::Quad { vertices, .. } => {
+                AcousticMesh::quad(vertices.map(Vec3::from_array), material)
+            }
+            Shape::Mesh {
+                vertices,
+                triangles,
+            } => AcousticMesh {
+                vertices: vertices.iter().copied().map(Vec3::from_array).collect(),
+                triangles: triangles
+                    .iter()
+                    .map(|triangle| triangle.map(|index| index as i32))
+                    .collect(),
+                material_indices: vec![0; triangles.len()],
+                materials: vec![material],
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SoundSource {
+    /// Path of the clip or `.cue.ron` sound cue, relative to the assets directory.
+    pub clip: String,
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub repeating: bool,
+    #[serde(default = "SoundSource::default_volume")]
+    pub volume: f32,
+    /// See [`audio::AudioSource::pitch`].
+    #[serde(default = "SoundSource::default_pitch")]
+    pub pitch: f32,
+    /// Name of the [`Mixer`] bus the source is routed to, the master bus by default.
+    #[serde(default)]
+    pub bus: Option<String>,
+    /// `false` plays the clip straight to the output as an [`audio::NonSpatialAudioSource`],
+    /// ignoring `position` and the acoustic properties below.
+    #[serde(default = "SoundSource::default_spatial")]
+    pub spatial: bool,
+    /// See [`audio::NonSpatialAudioSource::pan`].
+    #[serde(default)]
+    pub pan: f32,
+    /// See [`audio::AudioSource::priority`].
+    #[serde(default)]
+    pub priority: i32,
+    /// See [`audio::AudioSource::spread`].
+    #[serde(default = "SoundSource::default_spread")]
+    pub spread: f32,
+    /// See [`audio::AudioSource::occlusion_radius`].
+    #[serde(default)]
+    pub occlusion_radius: Option<f32>,
+    /// See [`audio::Directivity`]. Omnidirectional by default.
+    #[serde(default)]
+    pub dipole_weight: f32,
+    #[serde(default)]
+    pub dipole_power: f32,
+}
+
+impl SoundSource {
+    fn default_volume() -> f32 {
+        1.0
+    }
+
+    fn default_pitch() -> f32 {
+        1.0
+    }
+
+    fn default_spread() -> f32 {
+        1.0
+    }
+
+    fn default_spatial() -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Light {
+    pub position: [f32; 3],
+    pub intensity: f32, // Lumens
+    pub color: [f32; 3],
+}
+
+/// A box moving back and forth, acting as a moving occluder.
+#[derive(Debug, Deserialize)]
+pub struct SlidingDoorDescriptor {
+    pub size: [f32; 3],
+    pub closed: [f32; 3],
+    pub open: [f32; 3],
+    pub period: f32, // Seconds for a full open-close cycle.
+    pub material: String,
+}
+
+/// glTF scene spawned with the level, whose meshes are also used as acoustic geometry.
+#[derive(Debug, Deserialize)]
+pub struct GltfScene {
+    /// Path of the glTF file, relative to the assets directory.
+    pub path: String,
+    /// Acoustic material names keyed by glTF material name.
+    #[serde(default)]
+    pub materials: HashMap<String, String>,
+    /// Acoustic material name for glTF materials missing from `materials`.
+    #[serde(default = "GltfScene::default_material")]
+    pub default_material: String,
+}
+
+impl GltfScene {
+    fn default_material() -> String {
+        "generic".to_string()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LevelLoaderError {
+    #[error("could not read level: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse level: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("unknown acoustic material {0:?}")]
+    UnknownMaterial(String),
+    #[error("ambisonic clip {0:?} is played by a spatial source, set `spatial: false` to play it as a bed")]
+    SpatialAmbisonicClip(String),
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    type Asset = Level;
+    type Settings = ();
+    type Error = LevelLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let level: Level = ron::de::from_bytes(&bytes)?;
+
+        let material_names = level
+            .surfaces
+            .iter()
+            .map(|surface| &surface.material)
+            .chain(level.sliding_doors.iter().map(|door| &door.material))
+            .chain(level.gltf_scenes.iter().flat_map(|gltf_scene| {
+                gltf_scene
+                    .materials
+                    .values()
+                    .chain(std::iter::once(&gltf_scene.default_material))
+            }));
+        for material_name in material_names {
+            if level.material(material_name).is_none() {
+                return Err(LevelLoaderError::UnknownMaterial(material_name.clone()));
+            }
+        }
+        if let Some(source) = level
+            .sources
+            .iter()
+            .find(|source| source.spatial && source.clip.ends_with(".ambix.wav"))
+        {
+            return Err(LevelLoaderError::SpatialAmbisonicClip(source.clip.clone()));
+        }
+
+        Ok(level)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+/// Display name and asset path of every level, in key order.
+pub const LEVELS: [(&str, &str); 3] = [
+    ("Reflections", "levels/reflections.level.ron"),
+    ("Direct Sound", "levels/direct.level.ron"),
+    ("Reverb", "levels/reverb.level.ron"),
+];
+
+/// Keys selecting the level with the same index in [`LEVELS`].
+const LEVEL_KEYS: [KeyCode; 3] = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3];
+
+/// Handles to every level in [`LEVELS`], loaded upfront so switching is instant.
+#[derive(Resource)]
+pub struct Levels(pub Vec<Handle<Level>>);
+
+/// The level currently selected, and what was spawned for it.
+#[derive(Resource)]
+pub struct CurrentLevel {
+    pub index: usize,
+    /// Entity every spawned entity of the level is parented to, once spawned.
+    pub root: Option<Entity>,
+    pub surfaces: Vec<SpawnedSurface>,
+    pub surface_material: Handle<StandardMaterial>,
+}
+
+/// A [`Surface`] of the current level, along with what was built from it.
+pub struct SpawnedSurface {
+    pub surface: Surface,
+    pub material: audionimbus::Material,
+    pub entity: Entity,
+    pub static_mesh: StaticMeshId,
+}
+
+impl SpawnedSurface {
+    fn spawn(
+        commands: &mut Commands,
+        meshes: &mut Assets<Mesh>,
+        audio: &mut Audio,
+        root: Entity,
+        surface_material: Handle<StandardMaterial>,
+        surface: &Surface,
+        material: audionimbus::Material,
+    ) -> Self {
+        let entity = commands
+            .spawn((
+                Name::new(surface.name.clone()),
+                Mesh3d(meshes.add(surface.shape.visual_mesh())),
+                MeshMaterial3d(surface_material),
+                ChildOf(root),
+            ))
+            .id();
+        let static_mesh = audio.add_static_mesh(surface.shape.acoustic_mesh(material));
+
+        Self {
+            surface: surface.clone(),
+            material,
+            entity,
+            static_mesh,
+        }
+    }
+
+    fn despawn(self, commands: &mut Commands, audio: &mut Audio) {
+        audio.remove_static_mesh(self.static_mesh);
+        commands.entity(self.entity).despawn();
+    }
+
+    fn matches(&self, surface: &Surface, material: &audionimbus::Material) -> bool {
+        self.surface == *surface && geometry::same_material(&self.material, material)
+    }
+}
+
+/// Parent of the entities spawned for the current level.
+#[derive(Component)]
+#[require(Transform, Visibility)]
+pub struct LevelRoot;
+
+/// Moves an entity back and forth between its closed and open positions.
+#[derive(Component)]
+pub struct SlidingDoor {
+    pub closed: Vec3,
+    pub open: Vec3,
+    pub period: f32, // Seconds for a full open-close cycle.
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn load_levels(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+    ) {
+        commands.insert_resource(Levels(
+            LEVELS
+                .iter()
+                .map(|(_, path)| asset_server.load(*path))
+                .collect(),
+        ));
+        commands.insert_resource(CurrentLevel {
+            index: 0,
+            root: None,
+            surfaces: Vec::new(),
+            surface_material: materials.add(StandardMaterial {
+                base_color: Color::Srgba(bevy::color::palettes::basic::SILVER),
+                double_sided: true,
+                cull_mode: None,
+                ..default()
+            }),
+        });
+    }
+
+    fn switch_level(
+        mut commands: Commands,
+        key_input: Res<ButtonInput<KeyCode>>,
+        mut current_level: ResMut<CurrentLevel>,
+        mut audio: ResMut<Audio>,
+        query_children: Query<&Children>,
+        query_gltf_acoustics: Query<&GltfAcoustics>,
+    ) {
+        let Some(index) = LEVEL_KEYS
+            .iter()
+            .position(|key| key_input.just_pressed(*key))
+        else {
+            return;
+        };
+        if index == current_level.index {
+            return;
+        }
+
+        if let Some(root) = current_level.root.take() {
+            // Dynamic meshes and sources are removed from the scene and the simulator when their
+            // entities are despawned.
+            for entity in query_children.iter_descendants(root) {
+                if let Ok(gltf_acoustics) = query_gltf_acoustics.get(entity) {
+                    for static_mesh in &gltf_acoustics.static_meshes {
+                        audio.remove_static_mesh(*static_mesh);
+                    }
+                }
+            }
+
+            for surface in current_level.surfaces.drain(..) {
+                audio.remove_static_mesh(surface.static_mesh);
+            }
+            audio.scene.commit();
+
+            commands.entity(root).despawn();
+        }
+
+        info!("Switching to level {}", LEVELS[index].0);
+        current_level.index = index;
+    }
+
+    fn spawn_level(
+        mut commands: Commands,
+        mut current_level: ResMut<CurrentLevel>,
+        level_handles: Res<Levels>,
+        levels: Res<Assets<Level>>,
+        asset_server: Res<AssetServer>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+        mut audio: ResMut<Audio>,
+        mixer: Res<Mixer>,
+        mut query_camera: Query<(&mut Transform, &mut CameraController)>,
+    ) {
+        if current_level.root.is_some() {
+            return;
+        }
+        let Some(level) = levels.get(&level_handles.0[current_level.index]) else {
+            return;
+        };
+
+        let root = commands
+            .spawn((Name::new(LEVELS[current_level.index].0), LevelRoot))
+            .id();
+
+        for surface in &level.surfaces {
+            // Materials are validated by the loader.
+            let material = level.material(&surface.material).unwrap();
+            let spawned_surface = SpawnedSurface::spawn(
+                &mut commands,
+                &mut meshes,
+                &mut audio,
+                root,
+                current_level.surface_material.clone(),
+                surface,
+                material,
+            );
+            current_level.surfaces.push(spawned_surface);
+        }
+        audio.scene.commit();
+
+        let door_material = materials.add(StandardMaterial {
+            base_color: Color::Srgba(bevy::color::palettes::basic::MAROON),
+            ..default()
+        });
+        for door in &level.sliding_doors {
+            let size = Vec3::from_array(door.size);
+            let closed = Vec3::from_array(door.closed);
+            commands.spawn((
+                Mesh3d(meshes.add(Cuboid::from_size(size))),
+                MeshMaterial3d(door_material.clone()),
+                Transform::from_translation(closed),
+                ChildOf(root),
+                SlidingDoor {
+                    closed,
+                    open: Vec3::from_array(door.open),
+                    period: door.period,
+                },
+                geometry::DynamicAcousticMesh {
+                    mesh: AcousticMesh::cuboid(size / 2.0, level.material(&door.material).unwrap()),
+                },
+            ));
+        }
+
+        for gltf_scene in &level.gltf_scenes {
+            commands.spawn((
+                SceneRoot(
+                    asset_server.load(GltfAssetLabel::Scene(0).from_asset(gltf_scene.path.clone())),
+                ),
+                GltfAcoustics {
+                    materials: gltf_scene
+                        .materials
+                        .iter()
+                        .map(|(gltf_material, material)| {
+                            (gltf_material.clone(), level.material(material).unwrap())
+                        })
+                        .collect(),
+                    default_material: level.material(&gltf_scene.default_material).unwrap(),
+                    static_meshes: Vec::new(),
+                },
+                ChildOf(root),
+            ));
+        }
+
+        let sphere = meshes.add(Sphere { radius: 0.1 });
+        let sphere_material = materials.add(StandardMaterial {
+            emissive: LinearRgba {
+                red: 0.0,
+                green: 0.0,
+                blue: 1000.0,
+                alpha: 1.0,
+            },
+            ..default()
+        });
+        for source in &level.sources {
+            let bus = match &source.bus {
+                Some(name) => mixer.find(name).unwrap_or_else(|| {
+                    warn!("Unknown bus {name} for {}, using master", source.clip);
+                    default()
+                }),
+                None => default(),
+            };
+            if !source.spatial {
+                commands.spawn((
+                    Name::new(source.clip.clone()),
+                    ChildOf(root),
+                    AudioBus(bus),
+                    audio::NonSpatialAudioSource {
+                        is_repeating: source.repeating,
+                        volume: source.volume,
+                        pitch: source.pitch,
+                        pan: source.pan,
+                        ..audio::NonSpatialAudioSource::new(Sound::load(
+                            &asset_server,
+                            &source.clip,
+                        ))
+                    },
+                ));
+                continue;
+            }
+
+            commands.spawn((
+                Mesh3d(sphere.clone()),
+                MeshMaterial3d(sphere_material.clone()),
+                Name::new(source.clip.clone()),
+                Transform::from_translation(Vec3::from_array(source.position)),
+                ChildOf(root),
+                AudioBus(bus),
+                audio::AudioSource {
+                    is_repeating: source.repeating,
+                    volume: source.volume,
+                    pitch: source.pitch,
+                    spread: source.spread,
+                    priority: source.priority,
+                    occlusion_radius: source.occlusion_radius,
+                    directivity: audio::Directivity {
+                        dipole_weight: source.dipole_weight,
+                        dipole_power: source.dipole_power,
+                    },
+                    ..audio::AudioSource::new(
+                        audio.create_source(
+                            audionimbus::SimulationFlags::DIRECT
+                                | audionimbus::SimulationFlags::REFLECTIONS,
+                        ),
+                        Sound::load(&asset_server, &source.clip),
+                    )
+                },
+            ));
+        }
+
+        for light in &level.lights {
+            let [red, green, blue] = light.color;
+            commands.spawn((
+                Transform::from_translation(Vec3::from_array(light.position)),
+                ChildOf(root),
+                PointLight {
+                    intensity: light.intensity,
+                    color: Color::srgb(red, green, blue),
+                    ..Default::default()
+                },
+            ));
+        }
+
+        commands.insert_resource(AmbientLight {
+            brightness: level.ambient_light,
+            ..Default::default()
+        });
+
+        for (mut transform, mut controller) in query_camera.iter_mut() {
+            *transform = Transform::from_translation(Vec3::from_array(level.camera.position));
+            controller.initialized = false;
+        }
+
+        current_level.root = Some(root);
+    }
+
+    /// Rebuilds the surfaces of the current level that changed on disk, leaving everything else
+    /// (camera, sources, doors, lights) untouched.
+    fn reload_level(
+        mut commands: Commands,
+        mut asset_events: MessageReader<AssetEvent<Level>>,
+        mut current_level: ResMut<CurrentLevel>,
+        level_handles: Res<Levels>,
+        levels: Res<Assets<Level>>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut audio: ResMut<Audio>,
+    ) {
+        let handle = &level_handles.0[current_level.index];
+        let modified = asset_events
+            .read()
+            .any(|event| event.is_modified(handle.id()));
+        if !modified {
+            return;
+        }
+        let Some(root) = current_level.root else {
+            return;
+        };
+        let Some(level) = levels.get(handle) else {
+            return;
+        };
+
+        let current_level = &mut *current_level;
+        let mut old_surfaces = std::mem::take(&mut current_level.surfaces);
+        let mut num_rebuilt = 0;
+        for surface in &level.surfaces {
+            let material = level.material(&surface.material).unwrap();
+            let unchanged = old_surfaces
+                .iter()
+                .position(|spawned_surface| spawned_surface.matches(surface, &material));
+            let spawned_surface = match unchanged {
+                Some(index) => old_surfaces.swap_remove(index),
+                None => {
+                    num_rebuilt += 1;
+                    SpawnedSurface::spawn(
+                        &mut commands,
+                        &mut meshes,
+                        &mut audio,
+                        root,
+                        current_level.surface_material.clone(),
+                        surface,
+                        material,
+                    )
+                }
+            };
+            current_level.surfaces.push(spawned_surface);
+        }
+
+        let num_removed = old_surfaces.len();
+        for spawned_surface in old_surfaces {
+            spawned_surface.despawn(&mut commands, &mut audio);
+        }
+
+        if num_rebuilt > 0 || num_removed > 0 {
+            audio.scene.commit();
+        }
+        info!(
+            "Reloaded level {}: {num_rebuilt} surfaces rebuilt, {num_removed} removed",
+            LEVELS[current_level.index].0
+        );
+    }
+
+    fn slide_doors(time: Res<Time>, mut query: Query<(&mut Transform, &SlidingDoor)>) {
+        for (mut transform, door) in query.iter_mut() {
+            let phase = time.elapsed_secs() * std::f32::consts::TAU / door.period;
+            let t = 0.5 - 0.5 * phase.cos();
+            transform.translation = door.closed.lerp(door.open, t);
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .add_systems(Startup, Self::load_levels)
+            .add_systems(
+                Update,
+                (
+                    (Self::switch_level, Self::spawn_level, Self::reload_level).chain(),
+                    Self::slide_doors,
+                ),
+            );
+    }
+}
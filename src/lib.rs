@@ -0,0 +1,23 @@
+//! Plugins of the demo, shared by the demo binary and the tests.
+
+pub mod audio;
+pub mod camera_controller;
+pub mod clip;
+pub mod clock;
+pub mod cue;
+pub mod debug_gizmos;
+pub mod dynamics;
+pub mod geometry;
+pub mod gltf_acoustics;
+pub mod inspector;
+pub mod level;
+pub mod lod;
+pub mod meter_overlay;
+pub mod metering;
+pub mod mixer;
+pub mod offline;
+pub mod play_sound;
+pub mod recording;
+pub mod resample;
+pub mod trajectory;
+pub mod voices;
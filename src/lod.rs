@@ -0,0 +1,119 @@
+//! Level of detail of the reflections of each source.
+//!
+//! Near and loud sources get convolution reflections, farther or quieter ones the cheaper
+//! parametric reverb built from the reverb times of the same hybrid simulation, and the farthest or
+//! quietest ones no reflections at all. Tiers only change how reflections are rendered: sources are
+//! created with reflections, so they stay in the ray tracing of the simulator whatever their tier.
+//!
+//! Sources must clear a margin past a threshold to change tiers, so that they don't flip back and
+//! forth around it, and their reflections are crossfaded over a frame when they do.
+
+use bevy::prelude::*;
+
+use crate::{
+    audio::{AudioSource, Listener},
+    metering::decibels,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LodTier {
+    /// Direct path only.
+    DirectOnly,
+    /// Direct path and parametric reflections.
+    Parametric,
+    /// Direct path and convolution reflections.
+    #[default]
+    Convolution,
+}
+
+#[derive(Resource, Debug)]
+pub struct LodSettings {
+    /// Farthest distance, in meters, at which sources get convolution reflections.
+    pub convolution_distance: f32,
+    /// Quietest direct path gain, in decibels, at which sources get convolution reflections.
+    pub convolution_min_gain_db: f32,
+    /// Farthest distance, in meters, at which sources get parametric reflections.
+    pub parametric_distance: f32,
+    /// Quietest direct path gain, in decibels, at which sources get parametric reflections.
+    pub parametric_min_gain_db: f32,
+    /// Fraction of the distance thresholds, and decibels of the gain thresholds, a source must go
+    /// past to leave its tier.
+    pub distance_hysteresis: f32,
+    pub gain_hysteresis_db: f32,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            convolution_distance: 20.0,
+            convolution_min_gain_db: -30.0,
+            parametric_distance: 50.0,
+            parametric_min_gain_db: -50.0,
+            distance_hysteresis: 0.1,
+            gain_hysteresis_db: 3.0,
+        }
+    }
+}
+
+impl LodSettings {
+    /// Tier of a source at `distance` from the listener with a direct path gain of `gain_db`,
+    /// currently at tier `current`.
+    pub fn tier(&self, current: LodTier, distance: f32, gain_db: f32) -> LodTier {
+        let qualifies = |tier: LodTier, max_distance: f32, min_gain_db: f32| {
+            // Staying in a tier, or above it, is easier than entering it.
+            if current >= tier {
+                distance <= max_distance * (1.0 + self.distance_hysteresis)
+                    && gain_db >= min_gain_db - self.gain_hysteresis_db
+            } else {
+                distance <= max_distance && gain_db >= min_gain_db
+            }
+        };
+
+        if qualifies(
+            LodTier::Convolution,
+            self.convolution_distance,
+            self.convolution_min_gain_db,
+        ) {
+            LodTier::Convolution
+        } else if qualifies(
+            LodTier::Parametric,
+            self.parametric_distance,
+            self.parametric_min_gain_db,
+        ) {
+            LodTier::Parametric
+        } else {
+            LodTier::DirectOnly
+        }
+    }
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn update_tiers(
+        listener: Res<Listener>,
+        mut query_audio_sources: Query<(&GlobalTransform, &mut AudioSource)>,
+        lod_settings: Res<LodSettings>,
+    ) {
+        let Some(listener_transform) = listener.transform else {
+            return;
+        };
+        let listener_position = listener_transform.translation();
+        for (transform, mut audio_source) in query_audio_sources.iter_mut() {
+            let distance = transform.translation().distance(listener_position);
+            let gain = audio_source.volume * audio_source.outputs.direct.gain();
+            audio_source.lod = lod_settings.tier(audio_source.lod, distance, decibels(gain));
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LodSettings>().add_systems(
+            PostUpdate,
+            Self::update_tiers
+                .after(crate::audio::Plugin::update_listener)
+                .before(crate::audio::Plugin::process_frame),
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Level meters of the master output, the effect buses, the mixer buses and every source, toggled
+//! with F3.
+//!
+//! Bars show the RMS level, the thin marker the held peak, and the master row counts samples
+//! that clipped before the master dynamics along with the gain reduction they applied.
+
+use bevy::prelude::*;
+
+use crate::{
+    audio::{AudioSource, NonSpatialAudioSource},
+    dynamics::MasterDynamics,
+    metering::{decibels, Meter, Meters},
+    mixer::{BusId, Mixer},
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F3;
+/// Level at the left end of the bars.
+const FLOOR_DB: f32 = -60.0;
+const BAR_WIDTH: f32 = 240.0;
+const BAR_HEIGHT: f32 = 10.0;
+
+/// Root node of the meter overlay.
+#[derive(Component)]
+struct MeterOverlay;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum MeterTarget {
+    Master,
+    Direct,
+    Reflections,
+    Reverb,
+    Bus(BusId),
+    Source(Entity),
+}
+
+/// Bar filled up to the RMS level of a meter.
+#[derive(Component)]
+struct RmsBar(MeterTarget);
+
+/// Marker at the peak level of a meter.
+#[derive(Component)]
+struct PeakMarker(MeterTarget);
+
+/// Text counting clipped samples and showing the gain reduction on the master output.
+#[derive(Component)]
+struct ClipCounter;
+
+/// Position of `amplitude` along a bar, from 0 to 100 percent.
+fn bar_percent(amplitude: f32) -> f32 {
+    ((decibels(amplitude) - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0) * 100.0
+}
+
+fn rms_color(rms: f32) -> Color {
+    if decibels(rms) > -6.0 {
+        Color::srgb(0.9, 0.8, 0.1)
+    } else {
+        Color::srgb(0.2, 0.8, 0.3)
+    }
+}
+
+fn peak_color(peak: f32) -> Color {
+    if peak > 1.0 {
+        Color::srgb(1.0, 0.1, 0.1)
+    } else {
+        Color::WHITE
+    }
+}
+
+fn spawn_row(parent: &mut ChildSpawnerCommands, label: String, target: MeterTarget) {
+    parent
+        .spawn(Node {
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(8.0),
+            ..default()
+        })
+        .with_children(|row| {
+            row.spawn((
+                Text::new(label),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                Node {
+                    width: Val::Px(120.0),
+                    ..default()
+                },
+            ));
+            row.spawn((
+                Node {
+                    width: Val::Px(BAR_WIDTH),
+                    height: Val::Px(BAR_HEIGHT),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb(0.15, 0.15, 0.15)),
+            ))
+            .with_children(|bar| {
+                bar.spawn((
+                    RmsBar(target),
+                    Node {
+                        position_type: PositionType::Absolute,
+                        height: Val::Percent(100.0),
+                        width: Val::Percent(0.0),
+                        ..default()
+                    },
+                    BackgroundColor(rms_color(0.0)),
+                ));
+                bar.spawn((
+                    PeakMarker(target),
+                    Node {
+                        position_type: PositionType::Absolute,
+                        height: Val::Percent(100.0),
+                        width: Val::Px(2.0),
+                        left: Val::Percent(0.0),
+                        ..default()
+                    },
+                    BackgroundColor(peak_color(0.0)),
+                ));
+            });
+            if target == MeterTarget::Master {
+                row.spawn((
+                    ClipCounter,
+                    Text::default(),
+                    TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                ));
+            }
+        });
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn setup(mut commands: Commands) {
+        commands.spawn((
+            MeterOverlay,
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(12.0),
+                right: Val::Px(12.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+            Visibility::Hidden,
+        ));
+    }
+
+    fn toggle(
+        key_input: Res<ButtonInput<KeyCode>>,
+        mut query_overlay: Query<&mut Visibility, With<MeterOverlay>>,
+    ) {
+        if !key_input.just_pressed(TOGGLE_KEY) {
+            return;
+        }
+        for mut visibility in query_overlay.iter_mut() {
+            *visibility = match *visibility {
+                Visibility::Hidden => Visibility::Visible,
+                _ => Visibility::Hidden,
+            };
+        }
+    }
+
+    /// Rebuilds the rows when sources or buses come and go.
+    fn rebuild_rows(
+        mut commands: Commands,
+        query_overlay: Single<Entity, With<MeterOverlay>>,
+        mixer: Res<Mixer>,
+        query_audio_sources: Query<
+            (Entity, Option<&Name>),
+            Or<(With<AudioSource>, With<NonSpatialAudioSource>)>,
+        >,
+        mut sources: Local<Vec<Entity>>,
+        mut num_buses: Local<usize>,
+        mut initialized: Local<bool>,
+    ) {
+        let current = query_audio_sources
+            .iter()
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        let current_num_buses = mixer.iter().count();
+        if *initialized && *sources == current && *num_buses == current_num_buses {
+            return;
+        }
+        *initialized = true;
+        *sources = current;
+        *num_buses = current_num_buses;
+
+        let overlay = query_overlay.into_inner();
+        commands
+            .entity(overlay)
+            .despawn_related::<Children>()
+            .with_children(|parent| {
+                spawn_row(parent, "Master".to_string(), MeterTarget::Master);
+                spawn_row(parent, "Direct".to_string(), MeterTarget::Direct);
+                spawn_row(parent, "Reflections".to_string(), MeterTarget::Reflections);
+                spawn_row(parent, "Reverb".to_string(), MeterTarget::Reverb);
+                // The master bus sums every source, as the master row does.
+                for (id, bus) in mixer.iter().filter(|(id, _)| *id != BusId::MASTER) {
+                    spawn_row(parent, format!("Bus {}", bus.name), MeterTarget::Bus(id));
+                }
+                for (entity, name) in query_audio_sources.iter() {
+                    spawn_row(
+                        parent,
+                        name.map_or_else(|| entity.to_string(), |name| name.to_string()),
+                        MeterTarget::Source(entity),
+                    );
+                }
+            });
+    }
+
+    fn update(
+        query_overlay: Query<&Visibility, With<MeterOverlay>>,
+        meters: Res<Meters>,
+        master_dynamics: Res<MasterDynamics>,
+        query_audio_sources: Query<(Option<&AudioSource>, Option<&NonSpatialAudioSource>)>,
+        mut query_rms_bars: Query<(&RmsBar, &mut Node, &mut BackgroundColor)>,
+        mut query_peak_markers: Query<
+            (&PeakMarker, &mut Node, &mut BackgroundColor),
+            Without<RmsBar>,
+        >,
+        mut query_clip_counter: Query<&mut Text, With<ClipCounter>>,
+    ) {
+        if query_overlay
+            .iter()
+            .all(|visibility| *visibility == Visibility::Hidden)
+        {
+            return;
+        }
+
+        let meter = |target: MeterTarget| -> Option<Meter> {
+            match target {
+                MeterTarget::Master => Some(meters.master),
+                MeterTarget::Direct => Some(meters.direct.meter),
+                MeterTarget::Reflections => Some(meters.reflections.meter),
+                MeterTarget::Reverb => Some(meters.reverb.meter),
+                MeterTarget::Bus(id) => meters.buses.get(id.index()).map(|bus| bus.meter),
+                MeterTarget::Source(entity) => match query_audio_sources.get(entity) {
+                    Ok((Some(audio_source), _)) => Some(audio_source.meter),
+                    Ok((None, Some(non_spatial_source))) => Some(non_spatial_source.meter),
+                    _ => None,
+                },
+            }
+        };
+
+        for (rms_bar, mut node, mut color) in query_rms_bars.iter_mut() {
+            let Some(meter) = meter(rms_bar.0) else {
+                continue;
+            };
+            node.width = Val::Percent(bar_percent(meter.rms));
+            color.0 = rms_color(meter.rms);
+        }
+        for (peak_marker, mut node, mut color) in query_peak_markers.iter_mut() {
+            let Some(meter) = meter(peak_marker.0) else {
+                continue;
+            };
+            node.left = Val::Percent(bar_percent(meter.peak));
+            color.0 = peak_color(meter.peak);
+        }
+        for mut text in query_clip_counter.iter_mut() {
+            let gain = master_dynamics.limiter.gain()
+                * master_dynamics
+                    .compressor
+                    .map_or(1.0, |compressor| compressor.gain());
+            text.0 = format!(
+                "{} clipped, GR {:.1} dB",
+                meters.master.clipped_samples,
+                decibels(gain)
+            );
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, Self::setup).add_systems(
+            Update,
+            (Self::toggle, Self::rebuild_rows, Self::update).chain(),
+        );
+    }
+}
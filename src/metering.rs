@@ -0,0 +1,146 @@
+//! Peak and RMS meters on the output mix.
+//!
+//! Meters are updated on the audio path once per frame, so nothing here allocates after
+//! construction.
+
+use bevy::prelude::*;
+
+use crate::{
+    audio::{FRAME_SIZE, NUM_CHANNELS, SAMPLING_RATE},
+    mixer::{BusId, Mixer},
+};
+
+/// How fast the displayed peak falls back after a transient.
+const PEAK_RELEASE_DB_PER_SECOND: f32 = 20.0;
+
+/// Level of a signal over the latest frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Meter {
+    /// Largest absolute sample, held and released at [`PEAK_RELEASE_DB_PER_SECOND`].
+    pub peak: f32,
+    /// Root mean square of the latest frame.
+    pub rms: f32,
+    /// Samples measured above full scale since the meter was created.
+    pub clipped_samples: u64,
+}
+
+impl Meter {
+    /// Updates the meter with the next frame of `samples`. Channels may be interleaved or
+    /// planar, since they are measured together.
+    pub fn measure(&mut self, samples: &[f32]) {
+        let mut peak = 0.0_f32;
+        let mut sum_of_squares = 0.0;
+        for sample in samples {
+            let magnitude = sample.abs();
+            peak = peak.max(magnitude);
+            sum_of_squares += sample * sample;
+            if magnitude > 1.0 {
+                self.clipped_samples += 1;
+            }
+        }
+
+        let frame_duration = FRAME_SIZE as f32 / SAMPLING_RATE as f32;
+        let release = 10.0_f32.powf(-PEAK_RELEASE_DB_PER_SECOND * frame_duration / 20.0);
+        self.peak = peak.max(self.peak * release);
+        self.rms = if samples.is_empty() {
+            0.0
+        } else {
+            (sum_of_squares / samples.len() as f32).sqrt()
+        };
+    }
+}
+
+/// Meter of a bus summed from several sources, accumulated in a buffer allocated up front.
+#[derive(Debug)]
+pub struct BusMeter {
+    buffer: Vec<f32>,
+    pub meter: Meter,
+}
+
+impl BusMeter {
+    pub fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len],
+            meter: Meter::default(),
+        }
+    }
+
+    /// Starts a new frame.
+    pub fn clear(&mut self) {
+        self.buffer.fill(0.0);
+    }
+
+    /// Adds `samples` scaled by `gain` to the frame.
+    pub fn add(&mut self, samples: &[f32], gain: f32) {
+        for (accumulated, sample) in self.buffer.iter_mut().zip(samples) {
+            *accumulated += sample * gain;
+        }
+    }
+
+    /// Measures the accumulated frame.
+    pub fn measure(&mut self) {
+        self.meter.measure(&self.buffer);
+    }
+}
+
+/// Meters of the effect buses, the [`Mixer`] buses and the master output.
+///
+/// Effect buses are metered on their omnidirectional ambisonic channel, before decoding, with
+/// the gain they are mixed at. Mixer buses are metered on the decoded stereo output of the sources
+/// routed to them or to the buses below them, after the bus gains. The master output is metered on the decoded stereo mix, before the
+/// [`MasterDynamics`](crate::dynamics::MasterDynamics), so that it shows what the limiter keeps
+/// from clipping.
+#[derive(Resource, Debug)]
+pub struct Meters {
+    pub direct: BusMeter,
+    pub reflections: BusMeter,
+    pub reverb: BusMeter,
+    /// Meters of the mixer buses, by [`BusId::index`].
+    pub buses: Vec<BusMeter>,
+    pub master: Meter,
+}
+
+impl Meters {
+    /// Starts a new frame on the mixer bus meters, adding meters for the buses added to `mixer`
+    /// since the last one.
+    pub fn clear_buses(&mut self, mixer: &Mixer) {
+        let num_buses = mixer.iter().count();
+        self.buses
+            .resize_with(num_buses, || BusMeter::new(FRAME_SIZE * NUM_CHANNELS));
+        for bus in &mut self.buses {
+            bus.clear();
+        }
+    }
+
+    /// Adds the decoded output of a source routed to `bus` to its meter and the meters of the
+    /// buses above it.
+    pub fn add_to_buses(&mut self, mixer: &Mixer, bus: BusId, samples: &[f32]) {
+        for id in mixer.path_to_master(bus) {
+            self.buses[id.index()].add(samples, 1.0);
+        }
+    }
+
+    /// Measures the accumulated frames of the mixer buses.
+    pub fn measure_buses(&mut self) {
+        for bus in &mut self.buses {
+            bus.measure();
+        }
+    }
+}
+
+impl Default for Meters {
+    fn default() -> Self {
+        Self {
+            direct: BusMeter::new(FRAME_SIZE),
+            reflections: BusMeter::new(FRAME_SIZE),
+            reverb: BusMeter::new(FRAME_SIZE),
+            buses: Vec::new(),
+            master: Meter::default(),
+        }
+    }
+}
+
+/// Converts a linear amplitude to decibels relative to full scale.
+pub fn decibels(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(1e-6).log10()
+}
@@ -0,0 +1,170 @@
+//! Headless app rendering the audio of the levels offline, for tests and benchmarks.
+//!
+//! ```ignore
+//! let mut app = offline::app();
+//! offline::load_level(&mut app, 0);
+//! let samples = offline::render(&mut app, 100, |_| Transform::default());
+//! ```
+//!
+//! The app has no window, GPU or output device, and runs in
+//! [`DeterministicMode`](crate::audio::DeterministicMode): each update renders exactly one frame,
+//! and the time advances by the duration of a frame, so renders don't depend on how fast they run.
+
+use std::time::{Duration, Instant};
+
+use bevy::{
+    log::LogPlugin,
+    prelude::*,
+    render::{settings::WgpuSettings, RenderPlugin},
+    time::TimeUpdateStrategy,
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+
+use crate::{
+    audio::{
+        self, Audio, AudioListener, AudioOutput, AudioSource, NonSpatialAudioSource, FRAME_SIZE,
+        SAMPLING_RATE,
+    },
+    camera_controller::CameraController,
+    cue::{self, Sound},
+    geometry, gltf_acoustics,
+    level::{self, CurrentLevel, LEVELS},
+    lod, voices,
+};
+
+/// Time given to a level and its clips to load.
+const LOADING_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Duration of a frame, by which the time advances at every update in deterministic mode.
+pub fn frame_duration() -> Duration {
+    Duration::from_secs_f64(FRAME_SIZE as f64 / SAMPLING_RATE as f64)
+}
+
+/// Builds an app with the plugins of the demo that make up the sound of the levels, and an
+/// [`AudioListener`] that the levels move to their camera spawn point.
+pub fn app() -> App {
+    app_with(audio::Plugin {
+        offline: true,
+        ..Default::default()
+    })
+}
+
+/// Like [`app`], with `audio_plugin` instead of the default offline one. It must have `offline`
+/// set.
+pub fn app_with(audio_plugin: audio::Plugin) -> App {
+    assert!(audio_plugin.offline, "audio is not rendered offline");
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                ..Default::default()
+            })
+            .set(RenderPlugin {
+                render_creation: WgpuSettings {
+                    backends: None,
+                    ..Default::default()
+                }
+                .into(),
+                ..Default::default()
+            })
+            .disable::<WinitPlugin>()
+            .disable::<LogPlugin>(),
+    )
+    .add_plugins(audio_plugin)
+    .add_plugins(cue::Plugin)
+    .add_plugins(geometry::Plugin)
+    .add_plugins(voices::Plugin)
+    .add_plugins(lod::Plugin)
+    .add_plugins(level::Plugin)
+    .add_plugins(gltf_acoustics::Plugin);
+
+    app.world_mut().spawn((
+        CameraController::default(),
+        AudioListener,
+        Transform::default(),
+    ));
+    app
+}
+
+/// Spawns the level at `index` in [`LEVELS`], and updates until it and its clips are loaded. The
+/// time stands still while loading, so nothing is rendered until the level is loaded.
+pub fn load_level(app: &mut App, index: usize) {
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+    // Runs the startup systems loading the levels.
+    app.update();
+    app.world_mut().resource_mut::<CurrentLevel>().index = index;
+
+    let start = Instant::now();
+    while !is_loaded(app.world_mut()) {
+        assert!(
+            start.elapsed() < LOADING_TIMEOUT,
+            "level {} did not load",
+            LEVELS[index].0
+        );
+        // Assets load on other threads.
+        std::thread::sleep(Duration::from_millis(10));
+        app.update();
+    }
+
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(frame_duration()));
+}
+
+fn is_loaded(world: &mut World) -> bool {
+    if world.resource::<CurrentLevel>().root.is_none() {
+        return false;
+    }
+
+    let asset_server = world.resource::<AssetServer>().clone();
+    let is_sound_loaded = |sound: &Sound| match sound {
+        Sound::Clip(clip) => asset_server.is_loaded_with_dependencies(clip),
+        Sound::Cue(cue) => asset_server.is_loaded_with_dependencies(cue),
+    };
+    world
+        .query::<&AudioSource>()
+        .iter(world)
+        .all(|audio_source| is_sound_loaded(&audio_source.sound))
+        && world
+            .query::<&NonSpatialAudioSource>()
+            .iter(world)
+            .all(|non_spatial_source| is_sound_loaded(&non_spatial_source.sound))
+}
+
+/// Renders `num_frames` frames, moving the listener to `listener_path(time)` before each one,
+/// `time` being the start of the frame in seconds since the start of the render. Returns the
+/// interleaved stereo output.
+pub fn render(
+    app: &mut App,
+    num_frames: usize,
+    listener_path: impl Fn(f32) -> Transform,
+) -> Vec<f32> {
+    for frame in 0..num_frames {
+        let time = (frame * FRAME_SIZE) as f32 / SAMPLING_RATE as f32;
+        let world = app.world_mut();
+        let mut listener_transform = world
+            .query_filtered::<&mut Transform, With<AudioListener>>()
+            .single_mut(world)
+            .unwrap();
+        *listener_transform = listener_path(time);
+        app.update();
+    }
+    take_output(app.world_mut())
+}
+
+/// Transform of the listener, such as where the level spawned it.
+pub fn listener_transform(app: &mut App) -> Transform {
+    let world = app.world_mut();
+    *world
+        .query_filtered::<&Transform, With<AudioListener>>()
+        .single(world)
+        .unwrap()
+}
+
+fn take_output(world: &mut World) -> Vec<f32> {
+    match &mut world.resource_mut::<Audio>().output {
+        AudioOutput::Offline(samples) => std::mem::take(samples),
+        AudioOutput::Device(_) => panic!("audio is not rendered offline"),
+    }
+}
@@ -0,0 +1,164 @@
+//! One-shot spatial sounds spawned from [`Commands`].
+//!
+//! ```ignore
+//! commands.play_sound(asset_server.load::<AudioClip>("impact.wav"), hit_position);
+//! commands.play_sound(asset_server.load::<SoundCue>("footsteps.cue.ron"), feet);
+//! commands.play_sound(PlaySound::new(clip).with_volume(0.5).with_pitch(1.2), position);
+//! commands.play_sound_on(asset_server.load("engine.wav"), car);
+//! commands.play_sound(PlaySound::new(clip).with_start(clock.after_secs(0.5)), position);
+//! ```
+//!
+//! Sounds are despawned, and leave the simulator, once their clip has played to the end. In the
+//! demo, left clicking plays a sound where the listener is looking.
+
+use bevy::prelude::*;
+
+use crate::{
+    audio::{Audio, AudioSource, Listener},
+    clip::AudioClip,
+    clock::PlaybackSchedule,
+    cue::{Sound, SoundCue},
+    mixer::{AudioBus, BusId},
+};
+
+const PLAY_BUTTON: MouseButton = MouseButton::Left;
+const DEMO_CLIP: &str = "piano.raw";
+const MAX_DISTANCE: f32 = 100.0;
+
+/// A one-shot sound and how to play it.
+#[derive(Clone, Debug)]
+pub struct PlaySound {
+    pub sound: Sound,
+    /// Linear gain applied to the clip.
+    pub volume: f32,
+    /// See [`AudioSource::pitch`].
+    pub pitch: f32,
+    pub bus: BusId,
+    pub schedule: PlaybackSchedule,
+}
+
+impl PlaySound {
+    pub fn new(sound: impl Into<Sound>) -> Self {
+        Self {
+            sound: sound.into(),
+            volume: 1.0,
+            pitch: 1.0,
+            bus: BusId::SFX,
+            schedule: PlaybackSchedule::default(),
+        }
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    pub fn with_pitch(mut self, pitch: f32) -> Self {
+        self.pitch = pitch;
+        self
+    }
+
+    pub fn with_bus(mut self, bus: BusId) -> Self {
+        self.bus = bus;
+        self
+    }
+
+    /// Starts the sound at a sample time of the [`AudioClock`](crate::clock::AudioClock).
+    pub fn with_start(mut self, start: u64) -> Self {
+        self.schedule.start = Some(start);
+        self
+    }
+
+    /// Cuts the sound at a sample time of the [`AudioClock`](crate::clock::AudioClock).
+    pub fn with_stop(mut self, stop: u64) -> Self {
+        self.schedule.stop = Some(stop);
+        self
+    }
+}
+
+impl From<Handle<AudioClip>> for PlaySound {
+    fn from(clip: Handle<AudioClip>) -> Self {
+        Self::new(clip)
+    }
+}
+
+impl From<Handle<SoundCue>> for PlaySound {
+    fn from(cue: Handle<SoundCue>) -> Self {
+        Self::new(cue)
+    }
+}
+
+pub trait PlaySoundCommandsExt {
+    /// Plays a sound once at `position` in world space.
+    fn play_sound(&mut self, sound: impl Into<PlaySound>, position: Vec3) -> EntityCommands<'_>;
+
+    /// Plays a sound once, following `parent` as it moves.
+    fn play_sound_on(&mut self, sound: impl Into<PlaySound>, parent: Entity) -> EntityCommands<'_>;
+}
+
+impl PlaySoundCommandsExt for Commands<'_, '_> {
+    fn play_sound(&mut self, sound: impl Into<PlaySound>, position: Vec3) -> EntityCommands<'_> {
+        let entity = self.spawn(Transform::from_translation(position)).id();
+        insert_audio_source(self, entity, sound.into());
+        self.entity(entity)
+    }
+
+    fn play_sound_on(&mut self, sound: impl Into<PlaySound>, parent: Entity) -> EntityCommands<'_> {
+        let entity = self.spawn((Transform::default(), ChildOf(parent))).id();
+        insert_audio_source(self, entity, sound.into());
+        self.entity(entity)
+    }
+}
+
+/// Creating the [`AudioSource`] needs the [`Audio`] resource, so it is deferred to a command.
+fn insert_audio_source(commands: &mut Commands, entity: Entity, sound: PlaySound) {
+    commands.queue(move |world: &mut World| {
+        let source = world.resource_mut::<Audio>().create_source(
+            audionimbus::SimulationFlags::DIRECT | audionimbus::SimulationFlags::REFLECTIONS,
+        );
+        if let Ok(mut entity) = world.get_entity_mut(entity) {
+            entity.insert((
+                AudioBus(sound.bus),
+                sound.schedule,
+                AudioSource {
+                    volume: sound.volume,
+                    pitch: sound.pitch,
+                    ..AudioSource::new(source, sound.sound)
+                },
+            ));
+        }
+    });
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn play_on_click(
+        mut commands: Commands,
+        mouse_input: Res<ButtonInput<MouseButton>>,
+        listener: Res<Listener>,
+        audio: Res<Audio>,
+        asset_server: Res<AssetServer>,
+    ) {
+        if !mouse_input.just_pressed(PLAY_BUTTON) {
+            return;
+        }
+        let Some(transform) = listener.transform else {
+            return;
+        };
+
+        let ray = Ray3d::new(transform.translation(), transform.forward());
+        let position = audio
+            .raycast(ray, MAX_DISTANCE)
+            .map_or_else(|| ray.get_point(MAX_DISTANCE), |hit| hit.point);
+        // Slightly in front of the surface, so the direct path is not occluded by it.
+        let position = position - *ray.direction * 0.1;
+        commands.play_sound(asset_server.load::<AudioClip>(DEMO_CLIP), position);
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, Self::play_on_click);
+    }
+}
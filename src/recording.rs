@@ -0,0 +1,145 @@
+//! Recording of the output, both decoded to stereo and as the ambisonic mix.
+//!
+//! While recording, the stereo output is written to `recording.wav`, and the second order
+//! ambisonic mix of the spatial sources and soundfield beds to `recording.ambix.wav` in the AmbiX
+//! convention: ACN channel order and SN3D normalization. The ambisonic recording can be decoded
+//! again for any listener orientation or speaker layout. Panned non-spatial sources have no
+//! direction and are left out of it. It is not limited like the stereo output, but delayed by the
+//! latency of the limiter so that both recordings line up.
+
+use std::{fs::File, io::BufWriter, path::Path};
+
+use bevy::prelude::*;
+
+use crate::{
+    audio::{AMBISONICS_NUM_CHANNELS, FRAME_SIZE, NUM_CHANNELS, SAMPLING_RATE},
+    dynamics::MasterDynamics,
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F5;
+const STEREO_PATH: &str = "recording.wav";
+const AMBISONIC_PATH: &str = "recording.ambix.wav";
+
+type WavWriter = hound::WavWriter<BufWriter<File>>;
+
+struct Recording {
+    stereo: WavWriter,
+    ambisonic: WavWriter,
+    /// Latest samples of each channel of the ambisonic mix, not yet written.
+    ambisonic_delay: Vec<Vec<f32>>,
+}
+
+#[derive(Resource, Default)]
+pub struct Recorder {
+    recording: Option<Recording>,
+}
+
+impl Recorder {
+    /// Starts writing the stereo output and the ambisonic mix to 32-bit float WAV files. The
+    /// ambisonic mix is delayed by `stereo_latency` samples, the delay of the stereo output behind
+    /// it.
+    pub fn start(
+        &mut self,
+        stereo_path: impl AsRef<Path>,
+        ambisonic_path: impl AsRef<Path>,
+        stereo_latency: usize,
+    ) -> Result<(), hound::Error> {
+        let spec = |channels: usize| hound::WavSpec {
+            channels: channels as u16,
+            sample_rate: SAMPLING_RATE as u32,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        self.recording = Some(Recording {
+            stereo: hound::WavWriter::create(stereo_path, spec(NUM_CHANNELS))?,
+            ambisonic: hound::WavWriter::create(ambisonic_path, spec(AMBISONICS_NUM_CHANNELS))?,
+            ambisonic_delay: vec![vec![0.0; stereo_latency]; AMBISONICS_NUM_CHANNELS],
+        });
+        Ok(())
+    }
+
+    /// Stops recording and finishes writing the files.
+    pub fn stop(&mut self) -> Result<(), hound::Error> {
+        if let Some(recording) = self.recording.take() {
+            recording.stereo.finalize()?;
+            recording.ambisonic.finalize()?;
+        }
+        Ok(())
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Writes a frame of the planar stereo output, and of the planar ambisonic mix in Steam
+    /// Audio's N3D normalization. Does nothing when not recording.
+    pub fn write(&mut self, stereo: &[f32], ambisonic: &[f32]) -> Result<(), hound::Error> {
+        let Some(recording) = &mut self.recording else {
+            return Ok(());
+        };
+
+        write_planar(&mut recording.stereo, stereo, |_| 1.0)?;
+        let ambisonic = recording
+            .ambisonic_delay
+            .iter_mut()
+            .zip(ambisonic.chunks_exact(FRAME_SIZE))
+            .flat_map(|(delay, channel)| {
+                delay.extend_from_slice(channel);
+                delay.drain(..FRAME_SIZE).collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        write_planar(&mut recording.ambisonic, &ambisonic, |acn| {
+            let degree = (acn as f32).sqrt() as usize;
+            1.0 / ((2 * degree + 1) as f32).sqrt()
+        })
+    }
+}
+
+/// Interleaves a planar buffer into `writer`, scaling each channel by `channel_gain`.
+fn write_planar(
+    writer: &mut WavWriter,
+    samples: &[f32],
+    channel_gain: impl Fn(usize) -> f32,
+) -> Result<(), hound::Error> {
+    let channels = samples.chunks_exact(FRAME_SIZE).collect::<Vec<_>>();
+    let gains = (0..channels.len()).map(channel_gain).collect::<Vec<_>>();
+    for i in 0..FRAME_SIZE {
+        for (channel, gain) in channels.iter().zip(&gains) {
+            writer.write_sample(channel[i] * gain)?;
+        }
+    }
+    Ok(())
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn toggle_recording(
+        key_input: Res<ButtonInput<KeyCode>>,
+        master_dynamics: Res<MasterDynamics>,
+        mut recorder: ResMut<Recorder>,
+    ) {
+        if !key_input.just_pressed(TOGGLE_KEY) {
+            return;
+        }
+
+        if recorder.is_recording() {
+            match recorder.stop() {
+                Ok(()) => info!("Saved recording to {STEREO_PATH} and {AMBISONIC_PATH}"),
+                Err(error) => error!("Failed to save recording: {error}"),
+            }
+        } else {
+            let stereo_latency = master_dynamics.limiter.latency();
+            match recorder.start(STEREO_PATH, AMBISONIC_PATH, stereo_latency) {
+                Ok(()) => info!("Recording to {STEREO_PATH} and {AMBISONIC_PATH}"),
+                Err(error) => error!("Failed to start recording: {error}"),
+            }
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, Self::toggle_recording);
+    }
+}
@@ -0,0 +1,63 @@
+//! Band-limited interpolation of clips, used to play them at any rate.
+//!
+//! Samples between those of a clip are reconstructed with a windowed sinc kernel. When a clip is
+//! played faster than its sampling rate, the kernel is widened to low-pass the clip below the
+//! output Nyquist frequency, so that its higher frequencies don't alias.
+
+use std::sync::LazyLock;
+
+/// Zero crossings of the kernel on each side of its center, at a cutoff of 1.
+const HALF_ZERO_CROSSINGS: usize = 16;
+/// Kernel values tabulated per zero crossing. Values in between are linearly interpolated.
+const TABLE_RESOLUTION: usize = 256;
+
+/// Blackman windowed sinc from 0 to [`HALF_ZERO_CROSSINGS`], the kernel being symmetric.
+static KERNEL: LazyLock<Vec<f32>> = LazyLock::new(|| {
+    let len = HALF_ZERO_CROSSINGS * TABLE_RESOLUTION;
+    (0..=len)
+        .map(|i| {
+            let x = i as f64 / TABLE_RESOLUTION as f64;
+            let sinc = if i == 0 {
+                1.0
+            } else {
+                (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x)
+            };
+            // Window centered on the kernel and spanning both of its sides.
+            let phase = std::f64::consts::PI * (i + len) as f64 / len as f64;
+            let window = 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+            (sinc * window) as f32
+        })
+        .collect()
+});
+
+fn kernel(x: f32) -> f32 {
+    let position = x.abs() * TABLE_RESOLUTION as f32;
+    let index = position as usize;
+    if index >= KERNEL.len() - 1 {
+        return 0.0;
+    }
+    let fraction = position - index as f32;
+    KERNEL[index] * (1.0 - fraction) + KERNEL[index + 1] * fraction
+}
+
+/// Reads the signal at fractional `time`, in samples, given its samples at integer times.
+///
+/// `step` is the number of samples the signal advances per output sample. Above 1 the signal is
+/// low-passed at `1 / step` times its Nyquist frequency.
+pub fn interpolate(sample: impl Fn(i64) -> f32, time: f64, step: f64) -> f32 {
+    let center = time.floor() as i64;
+    let fraction = (time - center as f64) as f32;
+    let cutoff = (1.0 / step).min(1.0) as f32;
+    if fraction == 0.0 && cutoff == 1.0 {
+        // Exactly on a sample, which the kernel would return as is.
+        return sample(center);
+    }
+
+    let half_width = (HALF_ZERO_CROSSINGS as f32 / cutoff).ceil() as i64;
+    let mut sum = 0.0;
+    for index in center - half_width + 1..=center + half_width {
+        let distance = (index - center) as f32 - fraction;
+        sum += sample(index) * kernel(distance * cutoff);
+    }
+    sum * cutoff
+}
@@ -0,0 +1,266 @@
+//! Recording and replay of camera trajectories, to compare settings along the exact same walk.
+//!
+//! F6 starts recording the position, yaw and pitch of the [`CameraController`] at every frame, and
+//! stops and saves it to `camera.trajectory.ron`. F7 replays that file, driving the camera instead
+//! of the input: space pauses and resumes, and the left and right arrows scrub backward and
+//! forward. In [`DeterministicMode`](crate::audio::DeterministicMode), a frame is recorded per
+//! audio frame.
+//!
+//! Offline renders can follow a recorded walk too:
+//!
+//! ```ignore
+//! let trajectory = Trajectory::load("camera.trajectory.ron")?;
+//! let num_frames = (trajectory.duration() * SAMPLING_RATE as f32) as usize / FRAME_SIZE;
+//! offline::render(&mut app, num_frames, |time| trajectory.transform(time));
+//! ```
+
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::camera_controller::CameraController;
+
+const RECORD_KEY: KeyCode = KeyCode::F6;
+const PLAY_KEY: KeyCode = KeyCode::F7;
+const PAUSE_KEY: KeyCode = KeyCode::Space;
+const SCRUB_BACKWARD_KEY: KeyCode = KeyCode::ArrowLeft;
+const SCRUB_FORWARD_KEY: KeyCode = KeyCode::ArrowRight;
+/// Seconds skipped per press of a scrub key.
+const SCRUB_STEP: f32 = 1.0;
+const PATH: &str = "camera.trajectory.ron";
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct TrajectoryFrame {
+    /// Seconds since the start of the recording.
+    pub time: f32,
+    pub position: [f32; 3],
+    /// Rotation around the vertical axis, in radians.
+    pub yaw: f32,
+    /// Rotation around the right axis, in radians.
+    pub pitch: f32,
+}
+
+#[derive(Debug, Error)]
+pub enum TrajectoryError {
+    #[error("could not access trajectory: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse trajectory: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not serialize trajectory: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+/// Camera frames, in chronological order.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Trajectory {
+    pub frames: Vec<TrajectoryFrame>,
+}
+
+impl Trajectory {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TrajectoryError> {
+        Ok(ron::de::from_bytes(&std::fs::read(path)?)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), TrajectoryError> {
+        let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, ron)?;
+        Ok(())
+    }
+
+    /// Time of the last frame, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.frames.last().map_or(0.0, |frame| frame.time)
+    }
+
+    /// Records a frame at `time` seconds.
+    pub fn push(&mut self, time: f32, transform: &Transform) {
+        let (yaw, pitch, _roll) = transform.rotation.to_euler(EulerRot::YXZ);
+        self.frames.push(TrajectoryFrame {
+            time,
+            position: transform.translation.to_array(),
+            yaw,
+            pitch,
+        });
+    }
+
+    /// Position, yaw and pitch at `time` seconds, interpolated between the frames around it.
+    /// Times before the first frame or after the last one are clamped to them.
+    pub fn sample(&self, time: f32) -> Option<(Vec3, f32, f32)> {
+        let next_index = self.frames.partition_point(|frame| frame.time <= time);
+        let Some(next) = self.frames.get(next_index) else {
+            let last = self.frames.last()?;
+            return Some((last.position.into(), last.yaw, last.pitch));
+        };
+        let Some(previous) = next_index.checked_sub(1).map(|index| &self.frames[index]) else {
+            return Some((next.position.into(), next.yaw, next.pitch));
+        };
+
+        let t = (time - previous.time) / (next.time - previous.time);
+        // Yaw wraps around at half turns, so it is interpolated the shortest way.
+        let yaw_delta = (next.yaw - previous.yaw + std::f32::consts::PI)
+            .rem_euclid(std::f32::consts::TAU)
+            - std::f32::consts::PI;
+        Some((
+            Vec3::from(previous.position).lerp(next.position.into(), t),
+            previous.yaw + yaw_delta * t,
+            previous.pitch + (next.pitch - previous.pitch) * t,
+        ))
+    }
+
+    /// Camera transform at `time` seconds, or the identity if there are no frames.
+    pub fn transform(&self, time: f32) -> Transform {
+        self.sample(time)
+            .map_or(Transform::IDENTITY, |(position, yaw, pitch)| {
+                Transform::from_translation(position).with_rotation(Quat::from_euler(
+                    EulerRot::YXZ,
+                    yaw,
+                    pitch,
+                    0.0,
+                ))
+            })
+    }
+}
+
+/// Trajectory being recorded, along with the time it started at.
+#[derive(Resource, Default)]
+pub struct TrajectoryRecorder {
+    pub recording: Option<(f32, Trajectory)>,
+}
+
+/// Trajectory driving the camera, and the playback position in it.
+#[derive(Resource, Default)]
+pub struct TrajectoryPlayer {
+    pub trajectory: Option<Trajectory>,
+    /// Seconds since the start of the trajectory.
+    pub time: f32,
+    pub is_paused: bool,
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn toggle_recording(
+        key_input: Res<ButtonInput<KeyCode>>,
+        time: Res<Time>,
+        mut recorder: ResMut<TrajectoryRecorder>,
+    ) {
+        if !key_input.just_pressed(RECORD_KEY) {
+            return;
+        }
+
+        match recorder.recording.take() {
+            Some((_, trajectory)) => match trajectory.save(PATH) {
+                Ok(()) => info!(
+                    "Saved {:.1} s camera trajectory to {PATH}",
+                    trajectory.duration()
+                ),
+                Err(error) => error!("Failed to save camera trajectory: {error}"),
+            },
+            None => {
+                info!("Recording camera trajectory");
+                recorder.recording = Some((time.elapsed_secs(), Trajectory::default()));
+            }
+        }
+    }
+
+    fn record_frame(
+        time: Res<Time>,
+        mut recorder: ResMut<TrajectoryRecorder>,
+        query_camera: Query<&Transform, With<CameraController>>,
+    ) {
+        // The time stands still while deterministic mode waits for the output device.
+        if time.delta().is_zero() {
+            return;
+        }
+        let Some((start, trajectory)) = &mut recorder.recording else {
+            return;
+        };
+        let Ok(transform) = query_camera.single() else {
+            return;
+        };
+        trajectory.push(time.elapsed_secs() - *start, transform);
+    }
+
+    fn control_playback(
+        key_input: Res<ButtonInput<KeyCode>>,
+        mut player: ResMut<TrajectoryPlayer>,
+        mut query_camera: Query<&mut CameraController>,
+    ) {
+        if key_input.just_pressed(PLAY_KEY) {
+            if player.trajectory.take().is_some() {
+                info!("Stopped camera trajectory playback");
+            } else {
+                match Trajectory::load(PATH) {
+                    Ok(trajectory) => {
+                        info!("Playing {:.1} s camera trajectory", trajectory.duration());
+                        *player = TrajectoryPlayer {
+                            trajectory: Some(trajectory),
+                            ..Default::default()
+                        };
+                    }
+                    Err(error) => error!("Failed to load camera trajectory: {error}"),
+                }
+            }
+            // The camera follows either the trajectory or the input.
+            for mut controller in query_camera.iter_mut() {
+                controller.enabled = player.trajectory.is_none();
+            }
+        }
+
+        let Some(duration) = player.trajectory.as_ref().map(Trajectory::duration) else {
+            return;
+        };
+        if key_input.just_pressed(PAUSE_KEY) {
+            player.is_paused = !player.is_paused;
+        }
+        if key_input.just_pressed(SCRUB_BACKWARD_KEY) {
+            player.time = (player.time - SCRUB_STEP).max(0.0);
+        }
+        if key_input.just_pressed(SCRUB_FORWARD_KEY) {
+            player.time = (player.time + SCRUB_STEP).min(duration);
+        }
+    }
+
+    fn drive_camera(
+        time: Res<Time>,
+        mut player: ResMut<TrajectoryPlayer>,
+        mut query_camera: Query<(&mut Transform, &mut CameraController)>,
+    ) {
+        let player = &mut *player;
+        let Some(trajectory) = &player.trajectory else {
+            return;
+        };
+        let Some((position, yaw, pitch)) = trajectory.sample(player.time) else {
+            return;
+        };
+
+        for (mut transform, mut controller) in query_camera.iter_mut() {
+            transform.translation = position;
+            transform.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.0);
+            // Input resumes from where the playback stops.
+            controller.yaw = yaw;
+            controller.pitch = pitch;
+        }
+
+        if !player.is_paused {
+            player.time = (player.time + time.delta_secs()).min(trajectory.duration());
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrajectoryRecorder>()
+            .init_resource::<TrajectoryPlayer>()
+            .add_systems(
+                Update,
+                (
+                    Self::toggle_recording,
+                    (Self::control_playback, Self::drive_camera).chain(),
+                ),
+            )
+            .add_systems(PostUpdate, Self::record_frame);
+    }
+}
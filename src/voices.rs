@@ -0,0 +1,140 @@
+//! Voice management: only the most important sources are simulated.
+//!
+//! The simulator handles at most [`Audio::max_num_sources`] sources,
+//! [`MAX_NUM_SOURCES`](crate::audio::MAX_NUM_SOURCES) by default, one of which is the listener
+//! source used for reverb. Sources are ranked by priority, then by estimated audibility, and the
+//! top ones are made real voices: added to the simulator and processed. The others are virtual:
+//! out of the simulator and silent, but their playback position keeps advancing so they resume in
+//! time. Voices fade in and out when swapped partway through playback, while sources that become
+//! real before playing their first sample start at full gain, keeping their attack.
+
+use bevy::prelude::*;
+
+use crate::{
+    audio::{Audio, AudioSource, Listener, FRAME_SIZE, SAMPLING_RATE},
+    mixer::{AudioBus, Mixer},
+};
+
+/// Duration of the fade when a voice becomes real or virtual, in seconds.
+const FADE_TIME: f32 = 0.05;
+/// Audibility multiplier of real voices when ranking, so that sources of similar audibility don't
+/// keep swapping.
+const REAL_VOICE_BIAS: f32 = 1.25;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VoiceState {
+    /// Whether the source is in the simulator and processed.
+    pub is_real: bool,
+    /// Whether the source is ranked among the real voices. Real voices that are no longer wanted
+    /// fade out before leaving the simulator.
+    pub is_wanted: bool,
+    /// Fade gain, from 0 when silent to 1 when fully faded in.
+    pub gain: f32,
+}
+
+impl VoiceState {
+    /// Advances the fade by one frame. Returns the gains at the start and the end of the frame.
+    pub fn advance_fade(&mut self) -> (f32, f32) {
+        let start = self.gain;
+        let step = FRAME_SIZE as f32 / (FADE_TIME * SAMPLING_RATE as f32);
+        self.gain = if self.is_wanted {
+            (start + step).min(1.0)
+        } else {
+            (start - step).max(0.0)
+        };
+        (start, self.gain)
+    }
+
+    /// Whether the voice has faded out and can leave the simulator.
+    pub fn is_faded_out(&self) -> bool {
+        !self.is_wanted && self.gain == 0.0
+    }
+}
+
+/// Scales a planar buffer by a gain ramping linearly from `start` to `end` over each channel.
+pub fn apply_fade(samples: &mut [f32], (start, end): (f32, f32)) {
+    if start == 1.0 && end == 1.0 {
+        return;
+    }
+    for channel in samples.chunks_exact_mut(FRAME_SIZE) {
+        for (i, sample) in channel.iter_mut().enumerate() {
+            *sample *= start + (end - start) * i as f32 / FRAME_SIZE as f32;
+        }
+    }
+}
+
+pub struct Plugin;
+
+impl Plugin {
+    fn assign_voices(
+        listener: Res<Listener>,
+        mut query_audio_sources: Query<(
+            Entity,
+            &GlobalTransform,
+            &mut AudioSource,
+            Option<&AudioBus>,
+        )>,
+        mixer: Res<Mixer>,
+        mut audio: ResMut<Audio>,
+    ) {
+        let Some(listener_transform) = listener.transform else {
+            return;
+        };
+        let listener_position = listener_transform.translation();
+
+        let mut ranking = query_audio_sources
+            .iter()
+            .map(|(entity, transform, audio_source, audio_bus)| {
+                let distance = transform.translation().distance(listener_position);
+                let mut audibility = audio_source.volume
+                    * mixer.effective_gain(audio_bus.copied().unwrap_or_default().0)
+                    / distance.max(1.0);
+                if audio_source.voice.is_wanted {
+                    audibility *= REAL_VOICE_BIAS;
+                }
+                (entity, audio_source.priority, audibility)
+            })
+            .collect::<Vec<_>>();
+        ranking.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.total_cmp(&a.2)));
+
+        // Leaves room for the listener source.
+        let max_real_voices = audio.max_num_sources - 1;
+        let mut num_real_voices = query_audio_sources
+            .iter()
+            .filter(|(_, _, audio_source, _)| audio_source.voice.is_real)
+            .count();
+        let mut needs_commit = false;
+        for (rank, (entity, _, _)) in ranking.into_iter().enumerate() {
+            let (_, _, mut audio_source, _) = query_audio_sources.get_mut(entity).unwrap();
+            let has_played = audio_source.position != 0.0;
+            let voice = &mut audio_source.voice;
+            voice.is_wanted = rank < max_real_voices;
+
+            // Voices being faded out keep their slot until they leave the simulator.
+            if voice.is_wanted && !voice.is_real && num_real_voices < max_real_voices {
+                voice.is_real = true;
+                if !has_played {
+                    voice.gain = 1.0;
+                }
+                num_real_voices += 1;
+                audio.simulator.add_source(&audio_source.source);
+                needs_commit = true;
+            }
+        }
+
+        if needs_commit {
+            audio.simulator.commit();
+        }
+    }
+}
+
+impl bevy::app::Plugin for Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            Self::assign_voices
+                .after(crate::audio::Plugin::update_listener)
+                .before(crate::audio::Plugin::process_frame),
+        );
+    }
+}
@@ -0,0 +1,34 @@
+//! Offline renders of the levels, shared by the integration tests.
+
+use std::sync::Mutex;
+
+use audionimbus_demo::{
+    audio::{FRAME_SIZE, SAMPLING_RATE},
+    offline,
+};
+use bevy::prelude::*;
+
+const DURATION: f32 = 3.0;
+
+/// Levels are rendered one at a time, each simulation being heavy on its own.
+static RENDER_LOCK: Mutex<()> = Mutex::new(());
+
+/// Renders the level at `index` in [`LEVELS`](audionimbus_demo::level::LEVELS) with the listener
+/// walking forward from its spawn point at 1 m/s while turning left at 30°/s. Returns the
+/// interleaved stereo output.
+pub fn render_level(index: usize) -> Vec<f32> {
+    let _lock = RENDER_LOCK
+        .lock()
+        .unwrap_or_else(|error| error.into_inner());
+
+    let mut app = offline::app();
+    offline::load_level(&mut app, index);
+
+    let spawn = offline::listener_transform(&mut app);
+    let num_frames = (DURATION * SAMPLING_RATE as f32) as usize / FRAME_SIZE;
+    offline::render(&mut app, num_frames, |time| Transform {
+        translation: spawn.translation + spawn.forward() * time,
+        rotation: spawn.rotation * Quat::from_rotation_y(time * 30f32.to_radians()),
+        ..spawn
+    })
+}
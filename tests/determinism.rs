@@ -0,0 +1,28 @@
+//! Checks that rendering a level twice offline gives the same samples.
+//!
+//! Steam Audio exposes no seed for the rays it traces, so this is what tells whether
+//! [`DeterministicMode`](audionimbus_demo::audio::DeterministicMode) renders reproducibly.
+
+mod common;
+
+use audionimbus_demo::level::LEVELS;
+
+#[test]
+fn renders_are_reproducible() {
+    for (index, (name, _)) in LEVELS.iter().enumerate() {
+        let first = common::render_level(index);
+        let second = common::render_level(index);
+
+        assert_eq!(
+            first.len(),
+            second.len(),
+            "renders of {name} have different lengths"
+        );
+        // Compared bit for bit, and reported at the first difference rather than sample by sample.
+        let first_difference = first
+            .iter()
+            .zip(&second)
+            .position(|(a, b)| a.to_bits() != b.to_bits());
+        assert_eq!(first_difference, None, "renders of {name} differ");
+    }
+}
@@ -0,0 +1,185 @@
+//! Golden-file regression tests of the audio rendered in each level.
+//!
+//! Each level is rendered offline while the listener walks and turns along a fixed path, and the
+//! render is compared to a reference in `tests/golden`: on its energy over time, and on its
+//! energy per octave band. Renders are deterministic on a given machine, but not across CPUs and
+//! versions of Steam Audio, so they are compared within tolerances.
+//!
+//! A missing reference fails the test. References are written by running
+//! `UPDATE_GOLDEN=1 cargo test --test golden`, after a change that is meant to alter the sound, and
+//! should be listened to before being committed.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use audionimbus_demo::{
+    audio::{NUM_CHANNELS, SAMPLING_RATE},
+    level::LEVELS,
+};
+
+/// Duration of the segments whose energies are compared.
+const SEGMENT_DURATION: f32 = 0.25;
+/// Octave bands whose energies are compared, by center frequency.
+const OCTAVE_BANDS: [f32; 9] = [
+    63.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+/// Largest difference in energy allowed between a render and its reference.
+const TOLERANCE_DB: f32 = 3.0;
+/// Energies are clamped to this floor, so that differences in near silence are ignored.
+const FLOOR_DB: f32 = -60.0;
+
+#[test]
+fn reflections() {
+    check_level(0);
+}
+
+#[test]
+fn direct_sound() {
+    check_level(1);
+}
+
+#[test]
+fn reverb() {
+    check_level(2);
+}
+
+fn check_level(index: usize) {
+    let samples = common::render_level(index);
+
+    let path = reference_path(index);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        write_wav(&path, &samples);
+        eprintln!("Wrote reference render {}", path.display());
+        return;
+    }
+    assert!(
+        path.exists(),
+        "reference render {} is missing, write it with UPDATE_GOLDEN=1",
+        path.display()
+    );
+    let reference = read_wav(&path);
+    assert_eq!(
+        samples.len(),
+        reference.len(),
+        "render of {} and its reference have different lengths",
+        LEVELS[index].0
+    );
+
+    let mut failures = Vec::new();
+    for channel in 0..NUM_CHANNELS {
+        let render = deinterleave(&samples, channel);
+        let reference = deinterleave(&reference, channel);
+
+        let segment_len = (SEGMENT_DURATION * SAMPLING_RATE as f32) as usize;
+        for (segment, (render, reference)) in render
+            .chunks(segment_len)
+            .zip(reference.chunks(segment_len))
+            .enumerate()
+        {
+            compare(
+                &mut failures,
+                &format!("channel {channel}, segment {segment}"),
+                energy_db(render),
+                energy_db(reference),
+            );
+        }
+
+        for center in OCTAVE_BANDS {
+            compare(
+                &mut failures,
+                &format!("channel {channel}, {center} Hz band"),
+                energy_db(&band_pass(&render, center)),
+                energy_db(&band_pass(&reference, center)),
+            );
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "render of {} differs from its reference {}:\n{}",
+        LEVELS[index].0,
+        path.display(),
+        failures.join("\n")
+    );
+}
+
+fn reference_path(index: usize) -> PathBuf {
+    // "levels/reverb.level.ron" is stored as "reverb.wav".
+    let name = LEVELS[index].1.rsplit('/').next().unwrap();
+    let name = name.split('.').next().unwrap();
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.wav"))
+}
+
+fn compare(failures: &mut Vec<String>, what: &str, render_db: f32, reference_db: f32) {
+    let render_db = render_db.max(FLOOR_DB);
+    let reference_db = reference_db.max(FLOOR_DB);
+    if (render_db - reference_db).abs() > TOLERANCE_DB {
+        failures.push(format!(
+            "  {what}: {render_db:.1} dB instead of {reference_db:.1} dB"
+        ));
+    }
+}
+
+fn energy_db(samples: &[f32]) -> f32 {
+    let mean_square =
+        samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len().max(1) as f32;
+    10.0 * mean_square.max(f32::MIN_POSITIVE).log10()
+}
+
+fn deinterleave(samples: &[f32], channel: usize) -> Vec<f32> {
+    samples
+        .iter()
+        .skip(channel)
+        .step_by(NUM_CHANNELS)
+        .copied()
+        .collect()
+}
+
+/// Filters `samples` with a one octave wide band-pass biquad, of unit gain at `center` Hz.
+fn band_pass(samples: &[f32], center: f32) -> Vec<f32> {
+    let omega = std::f32::consts::TAU * center / SAMPLING_RATE as f32;
+    // Q of a one octave bandwidth.
+    let alpha = omega.sin() * (std::f32::consts::LN_2 / 2.0 * omega / omega.sin()).sinh();
+    let a0 = 1.0 + alpha;
+    let (b0, b2) = (alpha / a0, -alpha / a0);
+    let (a1, a2) = (-2.0 * omega.cos() / a0, (1.0 - alpha) / a0);
+
+    let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+    samples
+        .iter()
+        .map(|&x| {
+            let y = b0 * x + b2 * x2 - a1 * y1 - a2 * y2;
+            (x2, x1, y2, y1) = (x1, x, y1, y);
+            y
+        })
+        .collect()
+}
+
+fn write_wav(path: &Path, samples: &[f32]) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let mut writer = hound::WavWriter::create(
+        path,
+        hound::WavSpec {
+            channels: NUM_CHANNELS as u16,
+            sample_rate: SAMPLING_RATE as u32,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        },
+    )
+    .unwrap();
+    for &sample in samples {
+        writer.write_sample(sample).unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+fn read_wav(path: &Path) -> Vec<f32> {
+    hound::WavReader::open(path)
+        .unwrap()
+        .into_samples::<f32>()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}